@@ -2,14 +2,18 @@ mod test;
 
 use std::{
 	fs::File,
+	io::Write,
 	path::{Path, PathBuf},
 	str::FromStr,
 };
 
 use anyhow::Context;
 use argh::FromArgs;
-use gust_g1t::GustG1t;
-use gust_pak::GustPak;
+use gust_g1t::{errors::G1tReadError, GustG1t, TextureImage, TextureInfo};
+use gust_pak::{
+	common::GameVersion, EntryPattern, GustPak, MappedGustPak, PakEntryFilter, PakEntryRef,
+};
+use rayon::prelude::*;
 use test::TestSubCommand;
 use tracing::{debug, error, info, trace};
 
@@ -52,9 +56,47 @@ struct PakSubCommand {
 	#[argh(switch, short = 'l')]
 	pub list: bool,
 
-	/// the game version to use, eg. `A24` for Atelier Ryza 3
+	/// the game version to use, eg. `A24` for Atelier Ryza 3. detected automatically if not set
 	#[argh(option, short = 'g')]
-	pub game: String,
+	pub game: Option<String>,
+
+	/// the amount of files to extract at the same time, across all .pak files. `0` uses one job
+	/// per cpu core. defaults to 1
+	#[argh(option, short = 'j', default = "1")]
+	pub jobs: usize,
+
+	/// only extract or list files matching this glob, eg. `*.g1t` or `Data/x64/res_cmn/ui/*`.
+	/// paths use `/` and have no leading separator. can be used multiple times
+	#[argh(option)]
+	pub include: Vec<String>,
+
+	/// don't extract or list files matching this glob. can be used multiple times
+	#[argh(option)]
+	pub exclude: Vec<String>,
+
+	/// only extract or list files matching this regex, ignoring case. can be used multiple times
+	#[argh(option)]
+	pub include_regex: Vec<String>,
+
+	/// don't extract or list files matching this regex, ignoring case. can be used multiple times
+	#[argh(option)]
+	pub exclude_regex: Vec<String>,
+}
+
+impl PakSubCommand {
+	/// Builds the entry filter from the include and exclude options.
+	fn entry_filter(&self) -> anyhow::Result<PakEntryFilter> {
+		let parse_patterns = |globs: &[String], regexes: &[String]| {
+			let globs = globs.iter().map(|glob| EntryPattern::glob(glob));
+			let regexes = regexes.iter().map(|regex| EntryPattern::regex(regex));
+			globs.chain(regexes).collect::<Result<Vec<_>, _>>()
+		};
+
+		Ok(PakEntryFilter {
+			include: parse_patterns(&self.include, &self.include_regex)?,
+			exclude: parse_patterns(&self.exclude, &self.exclude_regex)?,
+		})
+	}
 }
 
 /// Extract .g1t files
@@ -68,6 +110,58 @@ struct G1tSubCommand {
 	/// the output directory
 	#[argh(positional)]
 	pub output: Option<PathBuf>,
+
+	/// the output format: `png` (default), `dds` or `ktx2`. dds and ktx2 files contain every
+	/// mipmap level and layer
+	#[argh(option, short = 'f', default = "OutputFormat::Png")]
+	pub format: OutputFormat,
+
+	/// also write every mipmap level, as `name_mipN.png`
+	#[argh(switch, short = 'm')]
+	pub mipmaps: bool,
+
+	/// rebuild the blue channel of normal maps, which are often only stored as red and green
+	#[argh(switch, short = 'n')]
+	pub normal_z: bool,
+
+	/// combine the frames or cubemap faces of a texture into a vertical strip, instead of writing
+	/// them as `name_frameN.png`, `name_faceN.png` or `name_frameN_faceM.png`. the strip holds
+	/// every frame from top to bottom, each with its faces in order
+	#[argh(switch, short = 's')]
+	pub strip: bool,
+
+	/// read the texture with this index as a cubemap with 6 faces per frame, for cubemaps that
+	/// aren't detected. only works with a single input file. can be used multiple times
+	#[argh(option)]
+	pub cubemap: Vec<usize>,
+
+	/// don't read the texture with this index as a cubemap, for textures that are wrongly
+	/// detected as one. only works with a single input file. can be used multiple times
+	#[argh(option)]
+	pub no_cubemap: Vec<usize>,
+}
+
+/// The file format textures are exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+	Png,
+	Dds,
+	Ktx2,
+}
+
+impl FromStr for OutputFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"png" => Ok(Self::Png),
+			"dds" => Ok(Self::Dds),
+			"ktx2" => Ok(Self::Ktx2),
+			_ => Err(format!(
+				"unknown output format: {s}, expected `png`, `dds` or `ktx2`"
+			)),
+		}
+	}
 }
 
 fn main() {
@@ -99,14 +193,22 @@ fn main() {
 }
 
 fn handle_pak(args: PakSubCommand) -> anyhow::Result<()> {
-	let Ok(game_version) = gust_pak::common::GameVersion::from_str(&args.game) else {
-		error!("Invalid game version: {}", args.game);
-		return Ok(());
+	let game_version = match &args.game {
+		Some(game) => {
+			let Ok(game_version) = GameVersion::from_str(game) else {
+				error!("Invalid game version: {}", game);
+				return Ok(());
+			};
+			info!("Using encryption keys for {}", game_version.get_name());
+			Some(game_version)
+		}
+		None => None,
 	};
-	info!("Using encryption keys for {}", game_version.get_name());
 
 	debug!("Pak file: {:?}", args.input);
 
+	let filter = args.entry_filter().context("parse file filters")?;
+
 	let input_files = if args.input.is_dir() {
 		let mut input_files = Vec::new();
 		for entry in std::fs::read_dir(&args.input)? {
@@ -115,7 +217,7 @@ fn handle_pak(args: PakSubCommand) -> anyhow::Result<()> {
 			if path.is_file()
 				&& path
 					.extension()
-					.map_or(false, |ext| ext.to_ascii_lowercase() == "pak")
+					.is_some_and(|ext| ext.eq_ignore_ascii_case("pak"))
 			{
 				input_files.push(path);
 			}
@@ -131,14 +233,20 @@ fn handle_pak(args: PakSubCommand) -> anyhow::Result<()> {
 		))?
 	};
 
+	let mut paks = vec![];
 	for input in input_files {
 		let mut file = File::open(&input)?;
 
-		let pak = GustPak::read_index(&mut file, game_version).context("read pak file")?;
-		info!("Found {} files in PAK file", pak.entries.len());
+		let game_version = match game_version {
+			Some(game_version) => game_version,
+			None => detect_game_version(&mut file, &input)?,
+		};
+
+		let pak = MappedGustPak::open(&file, game_version).context("read pak file")?;
+		info!("Found {} files in PAK file", pak.entries().len());
 
 		if args.list {
-			for entry in pak.entries.iter() {
+			for entry in pak.entries().iter_filtered(&filter) {
 				println!(
 					"- {} ({} bytes)",
 					entry.get_file_name(),
@@ -155,40 +263,108 @@ fn handle_pak(args: PakSubCommand) -> anyhow::Result<()> {
 		});
 		info!("Writing files to {}", output_path.to_string_lossy());
 
-		for entry in pak.entries.iter() {
-			let mut reader = entry
-				.get_reader(&mut file, &pak, game_version)
-				.context("get entry reader")?;
-
-			let entry_path = entry
-				.get_file_name()
-				.replace('\\', std::path::MAIN_SEPARATOR_STR);
-			let entry_path =
-				Path::new(entry_path.trim_start_matches(std::path::MAIN_SEPARATOR_STR));
-
-			let file_path = output_path.join(entry_path);
-			let file_directory = file_path.parent().context("file path has no parent")?;
-			std::fs::create_dir_all(file_directory).context("failed to create directory")?;
-
-			let mut file = match std::fs::File::create(&file_path) {
-				Ok(file) => file,
-				Err(e) => {
-					error!("Failed to create file: {}", e);
-					continue;
+		paks.push((input, pak, output_path));
+	}
+
+	let pool = rayon::ThreadPoolBuilder::new()
+		.num_threads(args.jobs)
+		.build()
+		.context("create thread pool")?;
+	debug!("Extracting with {} jobs", pool.current_num_threads());
+
+	// results are collected in order, so the summary doesn't depend on which job finishes first
+	let summaries = pool.install(|| {
+		paks.par_iter()
+			.map(|(input, pak, output_path)| {
+				let entries = pak.entries().iter_filtered(&filter).collect::<Vec<_>>();
+				let results = entries
+					.into_par_iter()
+					.map(|entry| {
+						extract_entry(pak, entry, output_path)
+							.map_err(|e| (entry.get_file_name().to_owned(), e))
+					})
+					.collect::<Vec<_>>();
+				(input, results)
+			})
+			.collect::<Vec<_>>()
+	});
+
+	let mut total_files = 0;
+	let mut total_bytes = 0;
+	let mut total_failures = 0;
+	for (input, results) in summaries {
+		let mut files = 0;
+		let mut failures = vec![];
+		for result in results {
+			match result {
+				Ok(bytes) => {
+					files += 1;
+					total_bytes += bytes;
 				}
-			};
+				Err(failure) => failures.push(failure),
+			}
+		}
 
-			debug!("Writing file: {:?}", file_path);
-			std::io::copy(&mut reader, &mut file).context("failed to write file")?;
+		info!(
+			"{}: extracted {} files, {} failed",
+			input.to_string_lossy(),
+			files,
+			failures.len()
+		);
+		for (file_name, e) in &failures {
+			error!("  {}: {:#}", file_name, e);
 		}
+
+		total_files += files;
+		total_failures += failures.len();
 	}
 
+	info!(
+		"Extracted {} files ({} bytes), {} failed",
+		total_files, total_bytes, total_failures
+	);
+
 	Ok(())
 }
 
+/// Extracts a single entry of a .pak file to the output directory, returning the amount of bytes
+/// written.
+fn extract_entry(
+	pak: &MappedGustPak,
+	entry: PakEntryRef<'_>,
+	output_path: &Path,
+) -> anyhow::Result<u64> {
+	let mut reader = pak.get_reader(entry).context("get entry reader")?;
+
+	let file_path = output_path.join(entry.get_normalized_file_name());
+	let file_directory = file_path.parent().context("file path has no parent")?;
+	std::fs::create_dir_all(file_directory).context("failed to create directory")?;
+
+	let mut file = File::create(&file_path).context("failed to create file")?;
+
+	debug!("Writing file: {:?}", file_path);
+	std::io::copy(&mut reader, &mut file).context("failed to write file")
+}
+
+/// Detects the game version of a .pak file, for when it was not passed on the command line.
+fn detect_game_version(file: &mut File, path: &Path) -> anyhow::Result<GameVersion> {
+	let game_version = GustPak::detect_game_version(file)
+		.with_context(|| format!("detect game version of {}", path.to_string_lossy()))?;
+	info!(
+		"Detected {} for {}",
+		game_version.get_name(),
+		path.to_string_lossy()
+	);
+	Ok(game_version)
+}
+
 fn handle_g1t(args: G1tSubCommand) -> anyhow::Result<()> {
 	debug!("g1t file: {:?}", args.input);
 
+	if args.input.is_dir() && !(args.cubemap.is_empty() && args.no_cubemap.is_empty()) {
+		anyhow::bail!("--cubemap and --no-cubemap only work with a single input file");
+	}
+
 	let input_files = if args.input.is_dir() {
 		let mut input_files = Vec::new();
 		for entry in std::fs::read_dir(&args.input)? {
@@ -197,7 +373,7 @@ fn handle_g1t(args: G1tSubCommand) -> anyhow::Result<()> {
 			if path.is_file()
 				&& path
 					.extension()
-					.map_or(false, |ext| ext.to_ascii_lowercase() == "g1t")
+					.is_some_and(|ext| ext.eq_ignore_ascii_case("g1t"))
 			{
 				input_files.push(path);
 			}
@@ -217,9 +393,22 @@ fn handle_g1t(args: G1tSubCommand) -> anyhow::Result<()> {
 		let mut file = File::open(&input)?;
 
 		debug!("reading g1t file...");
-		let g1t = GustG1t::read(&mut file).context("read g1t file")?;
+		let mut g1t = GustG1t::read(&mut file).context("read g1t file")?;
 		info!("Read g1t file");
 
+		let overrides = args
+			.cubemap
+			.iter()
+			.map(|&index| (index, true))
+			.chain(args.no_cubemap.iter().map(|&index| (index, false)));
+		for (index, cubemap) in overrides {
+			let texture = g1t
+				.textures
+				.get_mut(index)
+				.with_context(|| format!("texture {index} does not exist"))?;
+			texture.cubemap = cubemap;
+		}
+
 		let texture_count = g1t.textures.len();
 
 		if texture_count == 0 {
@@ -228,11 +417,6 @@ fn handle_g1t(args: G1tSubCommand) -> anyhow::Result<()> {
 		}
 
 		for (texture_index, texture) in g1t.textures.iter().enumerate() {
-			let image_bytes = g1t.read_image(texture, &mut file).context("read image")?;
-			let image_buffer =
-				image::RgbaImage::from_vec(texture.width, texture.height, image_bytes)
-					.context("image to rgbimage vec")?;
-
 			let output_dir = args.output.clone().unwrap_or_else(|| {
 				trace!("no output directory specified, using input directory");
 				input
@@ -241,26 +425,123 @@ fn handle_g1t(args: G1tSubCommand) -> anyhow::Result<()> {
 					.to_path_buf()
 			});
 
-			let texture_idx_string = (g1t.textures.len() > 1)
-				.then(|| format!("_{texture_index}"))
-				.unwrap_or_default();
-			let output_file_name = input
-				.file_stem()
-				.expect("get file stem")
-				.to_str()
-				.expect("file name to string")
-				.to_owned() + texture_idx_string.as_str()
-				+ ".png";
-
-			let output_path = output_dir.join(output_file_name);
-
-			debug!("saving image...");
-			image_buffer
-				.save_with_format(output_path, image::ImageFormat::Png)
-				.context("save file")?;
-			info!("Image saved");
+			let texture_idx_string = if g1t.textures.len() > 1 {
+				format!("_{texture_index}")
+			} else {
+				Default::default()
+			};
+
+			if matches!(args.format, OutputFormat::Dds | OutputFormat::Ktx2) {
+				let extension = match args.format {
+					OutputFormat::Dds => ".dds",
+					_ => ".ktx2",
+				};
+				let output_file_name = input
+					.file_stem()
+					.expect("get file stem")
+					.to_str()
+					.expect("file name to string")
+					.to_owned() + texture_idx_string.as_str()
+					+ extension;
+				let output_path = output_dir.join(output_file_name);
+
+				debug!("saving {extension} file...");
+				let output_file = File::create(&output_path).context("create output file")?;
+				let mut writer = std::io::BufWriter::new(output_file);
+				match args.format {
+					OutputFormat::Dds => g1t.write_dds(texture, &mut file, &mut writer),
+					_ => g1t.write_ktx2(texture, &mut file, &mut writer),
+				}
+				.context("write output file")?;
+				writer.flush().context("write output file")?;
+				info!("Texture saved");
+				continue;
+			}
+
+			let level_count = if args.mipmaps {
+				texture.header.mipmaps
+			} else {
+				1
+			};
+			for level in 0..level_count {
+				let (width, height) = texture.mipmap_dimensions(level);
+				let mut images = (0..texture.layer_count())
+					.map(|layer| {
+						let data = g1t.read_layer(texture, layer, level, &mut file)?;
+						Ok(TextureImage {
+							width,
+							height,
+							data,
+						})
+					})
+					.collect::<Result<Vec<_>, G1tReadError>>()
+					.context("read image")?;
+
+				if args.normal_z && texture.is_normal_map() {
+					debug!("reconstructing normal map z channel");
+					images
+						.iter_mut()
+						.for_each(|image| image.reconstruct_normal_z());
+				}
+
+				if args.strip && images.len() > 1 {
+					// stack all layers on top of each other, in the same order as they are named
+					images = vec![TextureImage {
+						width,
+						height: height * images.len() as u32,
+						data: images.into_iter().flat_map(|image| image.data).collect(),
+					}];
+				}
+
+				let image_count = images.len();
+				for (layer, image) in images.into_iter().enumerate() {
+					let layer_string = if image_count > 1 {
+						format!("_{}", layer_name(texture, layer as u32))
+					} else {
+						Default::default()
+					};
+					let mipmap_string = if level > 0 {
+						format!("_mip{level}")
+					} else {
+						Default::default()
+					};
+					let output_file_name = input
+						.file_stem()
+						.expect("get file stem")
+						.to_str()
+						.expect("file name to string")
+						.to_owned() + texture_idx_string.as_str()
+						+ layer_string.as_str()
+						+ mipmap_string.as_str()
+						+ ".png";
+
+					let output_path = output_dir.join(output_file_name);
+
+					let image_buffer =
+						image::RgbaImage::from_vec(image.width, image.height, image.data)
+							.context("image to rgbimage vec")?;
+
+					debug!("saving image...");
+					image_buffer
+						.save_with_format(output_path, image::ImageFormat::Png)
+						.context("save file")?;
+					info!("Image saved");
+				}
+			}
 		}
 	}
 
 	Ok(())
 }
+
+/// Returns the name of a layer of a texture, like `frame1`, `face2` or `frame1_face2` for
+/// cubemaps with multiple frames.
+fn layer_name(texture: &TextureInfo, layer: u32) -> String {
+	if !texture.is_cubemap() {
+		format!("frame{layer}")
+	} else if texture.frames > 1 {
+		format!("frame{}_face{}", layer / 6, layer % 6)
+	} else {
+		format!("face{layer}")
+	}
+}
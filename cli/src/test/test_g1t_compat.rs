@@ -11,7 +11,7 @@ use std::{
 use anyhow::Context;
 use argh::FromArgs;
 use gust_g1t::GustG1t;
-use gust_pak::GustPak;
+use gust_pak::{common::GameVersion, MappedGustPak};
 use rayon::prelude::*;
 use tracing::{debug, error, info};
 
@@ -22,18 +22,24 @@ pub struct TestG1tCompatibility {
 	#[argh(positional)]
 	pub input: std::path::PathBuf,
 
-	/// the game version to use, eg. `A24` for Atelier Ryza 3
+	/// the game version to use, eg. `A24` for Atelier Ryza 3. detected automatically if not set
 	#[argh(option, short = 'g')]
-	pub game: String,
+	pub game: Option<String>,
 }
 
 impl TestG1tCompatibility {
 	pub fn handle(&self) -> Result<(), anyhow::Error> {
-		let Ok(game_version) = gust_pak::common::GameVersion::from_str(&self.game) else {
-			error!("Invalid game version: {}", self.game);
-			return Ok(());
+		let game_version = match &self.game {
+			Some(game) => {
+				let Ok(game_version) = GameVersion::from_str(game) else {
+					error!("Invalid game version: {}", game);
+					return Ok(());
+				};
+				info!("Using encryption keys for {}", game_version.get_name());
+				Some(game_version)
+			}
+			None => None,
 		};
-		info!("Using encryption keys for {}", game_version.get_name());
 
 		if !self.input.exists() {
 			error!("input directory does not exist: {:?}", self.input);
@@ -73,34 +79,46 @@ impl TestG1tCompatibility {
 				}
 
 				debug!("Reading {:?}", item.path());
-				let file = std::fs::File::open(item.path()).context("open file")?;
-				let index = GustPak::read_index(&file, game_version).context("read index")?;
+				let mut file = std::fs::File::open(item.path()).context("open file")?;
+				let game_version = match game_version {
+					Some(game_version) => game_version,
+					None => crate::detect_game_version(&mut file, &item.path())?,
+				};
+				let pak = MappedGustPak::open(&file, game_version).context("read index")?;
+
+				// the pak is memory-mapped, so its entries can be read in parallel too
+				let g1t_entries = pak
+					.entries()
+					.iter()
+					.filter(|entry| entry.get_file_name().ends_with(".g1t"))
+					.collect::<Vec<_>>();
+				let results = g1t_entries
+					.into_par_iter()
+					.map(|entry| -> anyhow::Result<_> {
+						let file_name = entry.get_file_name();
+
+						let span = tracing::trace_span!("reading g1t file", file_name);
+						_ = span.enter();
+
+						let reader = pak.get_reader(entry)?;
+						let g1t = GustG1t::read(reader.clone())
+							.with_context(|| format!("read g1t file `{file_name}`"))?;
+
+						let unsupported_textures = g1t
+							.textures
+							.iter()
+							.filter_map(|texture| g1t.read_image(texture, reader.clone()).err())
+							.map(|e| (file_name.to_owned(), Cow::from(e.to_string())))
+							.collect::<Vec<_>>();
+						Ok((g1t.textures.len(), unsupported_textures))
+					})
+					.collect::<anyhow::Result<Vec<_>>>()?;
 
 				let mut unsupported_textures: Vec<(String, Cow<'static, str>)> = vec![];
 				let mut total_texture_count = 0;
-				for entry in index.entries.iter() {
-					let file_name = entry.get_file_name();
-					if !file_name.ends_with(".g1t") {
-						continue;
-					}
-
-					let span =
-						tracing::trace_span!("reading g1t file", file_name = entry.get_file_name());
-					_ = span.enter();
-
-					let reader = entry.get_reader(&file, &index, game_version)?;
-
-					let g1t = GustG1t::read(reader)
-						.with_context(|| format!("read g1t file `{file_name}`"))?;
-
-					for texture in &g1t.textures {
-						total_texture_count += 1;
-
-						let reader = entry.get_reader(&file, &index, game_version)?;
-						if let Err(e) = g1t.read_image(texture, reader) {
-							unsupported_textures.push((file_name.to_owned(), e.to_string().into()));
-						}
-					}
+				for (texture_count, unsupported) in results {
+					total_texture_count += texture_count;
+					unsupported_textures.extend(unsupported);
 				}
 
 				total_textures.fetch_add(total_texture_count, Ordering::Relaxed);
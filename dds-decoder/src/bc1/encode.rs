@@ -0,0 +1,87 @@
+use crate::util::{
+	closest_color, interp_color_2_opaque, interp_color_2_transparent, interp_color_3_opaque,
+	interp_color_3_transparent, range_fit, unpack_dxt_color_565,
+};
+
+/// Encodes a 4x4 block of RGBA8 pixels to BC1. Pixels with an alpha below 128 become transparent,
+/// the alpha of other pixels is lost.
+pub fn encode_block(pixels: &[[u8; 4]; 16], output: &mut [u8]) {
+	debug_assert_eq!(output.len(), 8);
+
+	let transparent = pixels.iter().any(|pixel| pixel[3] < 128);
+	output.copy_from_slice(&encode_color(pixels, transparent));
+}
+
+/// Encodes the color of a block, as used by both BC1 and BC3.
+///
+/// When `transparent` is set, the block uses the 3-color mode and pixels with an alpha below 128
+/// use the transparent index. Otherwise, the 4-color mode is used.
+pub(crate) fn encode_color(pixels: &[[u8; 4]; 16], transparent: bool) -> [u8; 8] {
+	let opaque_pixels = pixels
+		.iter()
+		.filter(|pixel| !transparent || pixel[3] >= 128)
+		.copied()
+		.collect::<Vec<_>>();
+
+	if opaque_pixels.is_empty() {
+		// every pixel is transparent
+		return [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+	}
+
+	let (start, end) = range_fit::<3>(&opaque_pixels);
+	let mut color_0 = pack_dxt_color_565(start);
+	let mut color_1 = pack_dxt_color_565(end);
+
+	// the order of the endpoints decides which mode is used
+	if transparent == (color_0 > color_1) {
+		std::mem::swap(&mut color_0, &mut color_1);
+	}
+
+	let unpacked_0 = unpack_dxt_color_565(color_0);
+	let unpacked_1 = unpack_dxt_color_565(color_1);
+	let palette = if transparent {
+		[
+			unpacked_0,
+			unpacked_1,
+			interp_color_2_transparent(unpacked_0, unpacked_1),
+			interp_color_3_transparent(unpacked_0, unpacked_1),
+		]
+	} else {
+		[
+			unpacked_0,
+			unpacked_1,
+			interp_color_2_opaque(unpacked_0, unpacked_1),
+			interp_color_3_opaque(unpacked_0, unpacked_1),
+		]
+	};
+
+	let mut color_bitmap = 0u32;
+	if color_0 != color_1 || transparent {
+		for (i, pixel) in pixels.iter().enumerate() {
+			let index = if transparent && pixel[3] < 128 {
+				3
+			} else if transparent {
+				closest_color(&palette[..3], pixel, 3).0
+			} else {
+				closest_color(&palette, pixel, 3).0
+			};
+			color_bitmap |= (index as u32) << (i * 2);
+		}
+	}
+
+	let [color_0_lo, color_0_hi] = color_0.to_le_bytes();
+	let [color_1_lo, color_1_hi] = color_1.to_le_bytes();
+	let [bitmap_0, bitmap_1, bitmap_2, bitmap_3] = color_bitmap.to_le_bytes();
+	[
+		color_0_lo, color_0_hi, color_1_lo, color_1_hi, bitmap_0, bitmap_1, bitmap_2, bitmap_3,
+	]
+}
+
+/// Packs an RGB color into a u16, rounding to the closest value that [unpack_dxt_color_565] can
+/// return.
+fn pack_dxt_color_565(color: [f32; 3]) -> u16 {
+	let red = u16::min(31, ((color[0] + 4.0) / 8.0) as u16);
+	let green = u16::min(63, ((color[1] + 2.0) / 4.0) as u16);
+	let blue = u16::min(31, ((color[2] + 4.0) / 8.0) as u16);
+	(red << 11) | (green << 5) | blue
+}
@@ -7,64 +7,29 @@
 // file generated by gust_tools in GIMP, it appears that this version is slightly less saturated. In
 // IrfanView, the effect seems to be reversed.
 
+pub(crate) mod encode;
+
+use std::convert::Infallible;
+
 use crate::{
 	util::{
-		interp_color_2_opaque, interp_color_2_transparent, interp_color_3_opaque,
+		decode_blocks, interp_color_2_opaque, interp_color_2_transparent, interp_color_3_opaque,
 		interp_color_3_transparent, unpack_dxt_color_565,
 	},
-	Color4, ColorBlock,
+	ColorBlock,
 };
 
-pub fn read_image(data: &[u8], width: usize, height: usize) -> Vec<u8> {
-	let blocks_x = usize::max(1, (width + 3) / 4);
-	let blocks_y = usize::max(1, (height + 3) / 4);
-	let block_count = blocks_x * blocks_y;
-	let decoded_pixel_count = block_count * 16;
-
+pub fn read_image(data: &[u8], width: usize, height: usize, output: &mut [u8], stride: usize) {
 	// BC1 has a chunk size of 8 bytes which decodes in a 4x4 block of pixels (64 bytes as RGBA8)
-	let mut decoded_pixels = vec![Color4::default(); decoded_pixel_count];
-	for (chunk_index, chunk) in data.chunks_exact(8).enumerate() {
-		let chunk = decode_block(chunk);
-
-		// copy the chunk into the decoded pixels
-		let chunk_x = (chunk_index % blocks_x) * 4;
-		let chunk_y = (chunk_index / blocks_x) * 4;
-		let target_index = chunk_y * (blocks_x * 4) + chunk_x;
-
-		#[allow(clippy::needless_range_loop)]
-		for y in 0..4 {
-			for x in 0..4 {
-				let target_index = target_index + y * (blocks_x * 4) + x;
-				decoded_pixels[target_index] = chunk[y][x];
-			}
-		}
-	}
-
-	let decoded_pixels = decoded_pixels
-		.into_iter()
-		.flat_map(|color| color.components)
-		.collect::<Vec<_>>();
-
-	// the decoded pixels may contain some "padding" on each row, since the width and height may not
-	// be divisible by the decoded block size
-	let mut final_pixels = vec![0u8; width * height * 4];
-	let decoded_pixels_line_bytes = blocks_x * 4 * 4;
-	decoded_pixels
-		.chunks_exact(decoded_pixels_line_bytes)
-		.take(height)
-		.enumerate()
-		.for_each(|(row, line)| {
-			let line_offset = width * row * 4;
-			final_pixels[line_offset..(line_offset + width * 4)]
-				.copy_from_slice(&line[0..width * 4]);
+	let Ok(()) =
+		decode_blocks::<Infallible, _>(data, width, height, 8, output, stride, |_, chunk| {
+			Ok(decode_block(chunk))
 		});
 
 	tracing::debug!("image decoded");
-
-	final_pixels
 }
 
-fn decode_block(block: &[u8]) -> ColorBlock {
+pub fn decode_block(block: &[u8]) -> ColorBlock {
 	debug_assert_eq!(block.len(), 8);
 
 	let color_0 = u16::from_le_bytes([block[0], block[1]]);
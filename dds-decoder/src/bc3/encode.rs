@@ -0,0 +1,37 @@
+use crate::bc1::encode::encode_color;
+
+/// Encodes a 4x4 block of RGBA8 pixels to BC3.
+pub fn encode_block(pixels: &[[u8; 4]; 16], output: &mut [u8]) {
+	debug_assert_eq!(output.len(), 16);
+
+	// the endpoints span the full alpha range, and alpha_0 > alpha_1 selects the 8-value palette
+	let alpha_0 = pixels.iter().map(|pixel| pixel[3]).max().unwrap_or(255) as usize;
+	let alpha_1 = pixels.iter().map(|pixel| pixel[3]).min().unwrap_or(255) as usize;
+
+	let mut alpha_bitmap = 0u64;
+	if alpha_0 > alpha_1 {
+		#[allow(clippy::identity_op)]
+		let alpha = [
+			alpha_0,
+			alpha_1,
+			(6 * alpha_0 + 1 * alpha_1 + 3) / 7,
+			(5 * alpha_0 + 2 * alpha_1 + 3) / 7,
+			(4 * alpha_0 + 3 * alpha_1 + 3) / 7,
+			(3 * alpha_0 + 4 * alpha_1 + 3) / 7,
+			(2 * alpha_0 + 5 * alpha_1 + 3) / 7,
+			(1 * alpha_0 + 6 * alpha_1 + 3) / 7,
+		];
+
+		for (i, pixel) in pixels.iter().enumerate() {
+			let index = (0..8)
+				.min_by_key(|&index| alpha[index].abs_diff(pixel[3] as usize))
+				.expect("palette is not empty");
+			alpha_bitmap |= (index as u64) << (i * 3);
+		}
+	}
+
+	output[0] = alpha_0 as u8;
+	output[1] = alpha_1 as u8;
+	output[2..8].copy_from_slice(&alpha_bitmap.to_le_bytes()[..6]);
+	output[8..16].copy_from_slice(&encode_color(pixels, false));
+}
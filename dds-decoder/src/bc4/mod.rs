@@ -0,0 +1,155 @@
+#![allow(clippy::needless_range_loop)]
+
+// This code is mostly based on the official microsoft documentation:
+// https://learn.microsoft.com/en-us/windows/win32/direct3d10/d3d10-graphics-programming-guide-resources-block-compression#bc4
+
+use std::convert::Infallible;
+
+use crate::{util::decode_blocks, Color4, ColorBlock};
+
+/// Decodes a BC4 image. The single channel is written to the red, green and blue components, so
+/// the result is a grayscale image.
+pub fn read_image(
+	data: &[u8],
+	width: usize,
+	height: usize,
+	signed: bool,
+	output: &mut [u8],
+	stride: usize,
+) {
+	// BC4 has a chunk size of 8 bytes which decodes in a 4x4 block of pixels (64 bytes as RGBA8)
+	let Ok(()) =
+		decode_blocks::<Infallible, _>(data, width, height, 8, output, stride, |_, chunk| {
+			Ok(decode_block(chunk, signed))
+		});
+
+	tracing::debug!("image decoded");
+}
+
+pub fn decode_block(block: &[u8], signed: bool) -> ColorBlock {
+	let channel = decode_channel(block, signed);
+
+	let mut color_block = ColorBlock::default();
+	for y in 0..4 {
+		for x in 0..4 {
+			let value = channel[y][x];
+			color_block[y][x] = Color4 {
+				components: [value, value, value, 255],
+			};
+		}
+	}
+
+	color_block
+}
+
+/// Decodes a single 8 byte channel block, as used by both BC4 and BC5.
+///
+/// Signed values are mapped from the -1 to 1 range to the 0 to 255 range, so they can be stored
+/// the same way as unsigned values.
+pub(crate) fn decode_channel(block: &[u8], signed: bool) -> [[u8; 4]; 4] {
+	debug_assert_eq!(block.len(), 8);
+
+	let palette = if signed {
+		// -128 and -127 both represent -1
+		let value_0 = i8::max(block[0] as i8, -127) as f32;
+		let value_1 = i8::max(block[1] as i8, -127) as f32;
+		interpolate(value_0, value_1, -127.0, 127.0).map(|value| {
+			let normalized = value / 127.0;
+			((normalized * 0.5 + 0.5) * 255.0).round() as u8
+		})
+	} else {
+		interpolate(block[0] as f32, block[1] as f32, 0.0, 255.0).map(|value| value.round() as u8)
+	};
+
+	// 3bpp 4x4 bitmap
+	let bitmap = u64::from_le_bytes([
+		block[2], block[3], block[4], block[5], block[6], block[7], 0, 0,
+	]);
+
+	let mut channel = [[0u8; 4]; 4];
+	for y in 0..4 {
+		for x in 0..4 {
+			let bits = (bitmap >> ((x + y * 4) * 3)) & 0b111;
+			channel[y][x] = palette[bits as usize];
+		}
+	}
+
+	channel
+}
+
+/// Builds the palette of 8 values from the 2 endpoints, like the alpha block of BC3.
+fn interpolate(value_0: f32, value_1: f32, min: f32, max: f32) -> [f32; 8] {
+	if value_0 > value_1 {
+		// six intermediate values created by interpolation between the two endpoints
+		[
+			value_0,
+			value_1,
+			(6.0 * value_0 + value_1) / 7.0,
+			(5.0 * value_0 + 2.0 * value_1) / 7.0,
+			(4.0 * value_0 + 3.0 * value_1) / 7.0,
+			(3.0 * value_0 + 4.0 * value_1) / 7.0,
+			(2.0 * value_0 + 5.0 * value_1) / 7.0,
+			(value_0 + 6.0 * value_1) / 7.0,
+		]
+	} else {
+		// four intermediate values, the last two values are the min and max value
+		[
+			value_0,
+			value_1,
+			(4.0 * value_0 + value_1) / 5.0,
+			(3.0 * value_0 + 2.0 * value_1) / 5.0,
+			(2.0 * value_0 + 3.0 * value_1) / 5.0,
+			(value_0 + 4.0 * value_1) / 5.0,
+			min,
+			max,
+		]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Creates a block where every pixel uses the given palette index.
+	fn block(value_0: u8, value_1: u8, index: u64) -> [u8; 8] {
+		let bitmap = (0..16).fold(0u64, |bitmap, i| bitmap | (index << (i * 3)));
+		let bitmap = bitmap.to_le_bytes();
+		[
+			value_0, value_1, bitmap[0], bitmap[1], bitmap[2], bitmap[3], bitmap[4], bitmap[5],
+		]
+	}
+
+	#[test]
+	fn decode_unsigned_palette() {
+		assert_eq!(decode_channel(&block(200, 100, 0), false)[0][0], 200);
+		assert_eq!(decode_channel(&block(200, 100, 1), false)[3][3], 100);
+		// (6 * 200 + 100) / 7 = 185.7
+		assert_eq!(decode_channel(&block(200, 100, 2), false)[1][2], 186);
+
+		// with value_0 <= value_1, the last 2 indices are 0 and 255
+		assert_eq!(decode_channel(&block(100, 200, 6), false)[0][0], 0);
+		assert_eq!(decode_channel(&block(100, 200, 7), false)[0][0], 255);
+		// (4 * 100 + 200) / 5 = 120
+		assert_eq!(decode_channel(&block(100, 200, 2), false)[0][0], 120);
+	}
+
+	#[test]
+	fn decode_signed_palette() {
+		// 127 is 1.0, -127 and -128 are -1.0 and 0 is 0.0
+		assert_eq!(decode_channel(&block(127, 0, 0), true)[0][0], 255);
+		assert_eq!(decode_channel(&block(0x81, 0, 0), true)[0][0], 0);
+		assert_eq!(decode_channel(&block(0x80, 0, 0), true)[0][0], 0);
+		assert_eq!(decode_channel(&block(0, 0x80, 0), true)[0][0], 128);
+
+		// 0x81 is -127, which is smaller than 0, so the 6-value palette is used
+		assert_eq!(decode_channel(&block(0x81, 0, 6), true)[0][0], 0);
+		assert_eq!(decode_channel(&block(0x81, 0, 7), true)[0][0], 255);
+	}
+
+	#[test]
+	fn read_image_is_grayscale() {
+		let mut image = [0; 2 * 2 * 4];
+		read_image(&block(200, 100, 0), 2, 2, false, &mut image, 2 * 4);
+		assert_eq!(image, [200, 200, 200, 255].repeat(4)[..]);
+	}
+}
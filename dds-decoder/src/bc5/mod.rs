@@ -0,0 +1,60 @@
+#![allow(clippy::needless_range_loop)]
+
+// This code is mostly based on the official microsoft documentation:
+// https://learn.microsoft.com/en-us/windows/win32/direct3d10/d3d10-graphics-programming-guide-resources-block-compression#bc5
+
+use std::convert::Infallible;
+
+use crate::{bc4::decode_channel, util::decode_blocks, Color4, ColorBlock};
+
+/// Decodes a BC5 image. The two channels are written to the red and green components, the blue
+/// component is left at 0.
+pub fn read_image(
+	data: &[u8],
+	width: usize,
+	height: usize,
+	signed: bool,
+	output: &mut [u8],
+	stride: usize,
+) {
+	// BC5 has a chunk size of 16 bytes which decodes in a 4x4 block of pixels (64 bytes as RGBA8)
+	let Ok(()) =
+		decode_blocks::<Infallible, _>(data, width, height, 16, output, stride, |_, chunk| {
+			Ok(decode_block(chunk, signed))
+		});
+
+	tracing::debug!("image decoded");
+}
+
+pub fn decode_block(block: &[u8], signed: bool) -> ColorBlock {
+	debug_assert_eq!(block.len(), 16);
+
+	// the red channel comes first, followed by the green channel
+	let red = decode_channel(&block[0..8], signed);
+	let green = decode_channel(&block[8..16], signed);
+
+	let mut color_block = ColorBlock::default();
+	for y in 0..4 {
+		for x in 0..4 {
+			color_block[y][x] = Color4 {
+				components: [red[y][x], green[y][x], 0, 255],
+			};
+		}
+	}
+
+	color_block
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn read_image_uses_red_and_green() {
+		// both channels use index 0 for every pixel
+		let block = [10, 0, 0, 0, 0, 0, 0, 0, 20, 0, 0, 0, 0, 0, 0, 0];
+		let mut image = [0; 4 * 4 * 4];
+		read_image(&block, 4, 4, false, &mut image, 4 * 4);
+		assert_eq!(image, [10, 20, 0, 255].repeat(16)[..]);
+	}
+}
@@ -0,0 +1,483 @@
+#![allow(clippy::needless_range_loop)]
+
+// This code is mostly based on the official microsoft documentation:
+// https://learn.microsoft.com/en-us/windows/win32/direct3d11/bc6h-format
+// The header layouts are taken from the D3D11 functional specification, the same way DirectXTex
+// describes them.
+
+use crate::bc7::blocks::{PARTITION2, TABLE_ANCHOR_INDEX_SECOND_SUBSET, WEIGHTS3, WEIGHTS4};
+
+/// A 4x4 block of RGB half floats, stored as their raw bits.
+pub type HalfBlock = [[[u16; 3]; 4]; 4];
+
+/// The endpoints in the specification are named w, x, y and z, these are their indices.
+const W: usize = 0;
+const X: usize = 1;
+const Y: usize = 2;
+const Z: usize = 3;
+
+/// A run of bits in the block header: `(endpoint, channel, lowest bit, bit count)`.
+///
+/// Some modes store bits of an endpoint in reverse order, these are listed as single bits.
+type Field = (usize, usize, u8, u8);
+
+// shorthands to keep the layout tables readable
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+
+struct ModeInfo {
+	/// Whether the block has 2 regions, otherwise it has 1.
+	two_regions: bool,
+	/// Whether the x, y and z endpoints are stored as a delta from w.
+	transformed: bool,
+	/// The precision of the w endpoint, which all endpoints are extended to.
+	endpoint_bits: u8,
+	/// The precision of the deltas for the r, g and b channels.
+	delta_bits: [u8; 3],
+	/// The endpoint fields, in the order they are stored in.
+	fields: &'static [Field],
+}
+
+#[rustfmt::skip]
+const MODES: [ModeInfo; 14] = [
+	// mode 1
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5],
+		fields: &[
+			(Y, G, 4, 1), (Y, B, 4, 1), (Z, B, 4, 1), (W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10),
+			(X, R, 0, 5), (Z, G, 4, 1), (Y, G, 0, 4), (X, G, 0, 5), (Z, B, 0, 1), (Z, G, 0, 4),
+			(X, B, 0, 5), (Z, B, 1, 1), (Y, B, 0, 4), (Y, R, 0, 5), (Z, B, 2, 1), (Z, R, 0, 5),
+			(Z, B, 3, 1),
+		],
+	},
+	// mode 2
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6],
+		fields: &[
+			(Y, G, 5, 1), (Z, G, 4, 1), (Z, G, 5, 1), (W, R, 0, 7), (Z, B, 0, 1), (Z, B, 1, 1),
+			(Y, B, 4, 1), (W, G, 0, 7), (Y, B, 5, 1), (Z, B, 2, 1), (Y, G, 4, 1), (W, B, 0, 7),
+			(Z, B, 3, 1), (Z, B, 5, 1), (Z, B, 4, 1), (X, R, 0, 6), (Y, G, 0, 4), (X, G, 0, 6),
+			(Z, G, 0, 4), (X, B, 0, 6), (Y, B, 0, 4), (Y, R, 0, 6), (Z, R, 0, 6),
+		],
+	},
+	// mode 3
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4],
+		fields: &[
+			(W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10), (X, R, 0, 5), (W, R, 10, 1), (Y, G, 0, 4),
+			(X, G, 0, 4), (W, G, 10, 1), (Z, B, 0, 1), (Z, G, 0, 4), (X, B, 0, 4), (W, B, 10, 1),
+			(Z, B, 1, 1), (Y, B, 0, 4), (Y, R, 0, 5), (Z, B, 2, 1), (Z, R, 0, 5), (Z, B, 3, 1),
+		],
+	},
+	// mode 4
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4],
+		fields: &[
+			(W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10), (X, R, 0, 4), (W, R, 10, 1), (Z, G, 4, 1),
+			(Y, G, 0, 4), (X, G, 0, 5), (W, G, 10, 1), (Z, G, 0, 4), (X, B, 0, 4), (W, B, 10, 1),
+			(Z, B, 1, 1), (Y, B, 0, 4), (Y, R, 0, 4), (Z, B, 0, 1), (Z, B, 2, 1), (Z, R, 0, 4),
+			(Y, G, 4, 1), (Z, B, 3, 1),
+		],
+	},
+	// mode 5
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5],
+		fields: &[
+			(W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10), (X, R, 0, 4), (W, R, 10, 1), (Y, B, 4, 1),
+			(Y, G, 0, 4), (X, G, 0, 4), (W, G, 10, 1), (Z, B, 0, 1), (Z, G, 0, 4), (X, B, 0, 5),
+			(W, B, 10, 1), (Y, B, 0, 4), (Y, R, 0, 4), (Z, B, 1, 1), (Z, B, 2, 1), (Z, R, 0, 4),
+			(Z, B, 4, 1), (Z, B, 3, 1),
+		],
+	},
+	// mode 6
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5],
+		fields: &[
+			(W, R, 0, 9), (Y, B, 4, 1), (W, G, 0, 9), (Y, G, 4, 1), (W, B, 0, 9), (Z, B, 4, 1),
+			(X, R, 0, 5), (Z, G, 4, 1), (Y, G, 0, 4), (X, G, 0, 5), (Z, B, 0, 1), (Z, G, 0, 4),
+			(X, B, 0, 5), (Z, B, 1, 1), (Y, B, 0, 4), (Y, R, 0, 5), (Z, B, 2, 1), (Z, R, 0, 5),
+			(Z, B, 3, 1),
+		],
+	},
+	// mode 7
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5],
+		fields: &[
+			(W, R, 0, 8), (Z, G, 4, 1), (Y, B, 4, 1), (W, G, 0, 8), (Z, B, 2, 1), (Y, G, 4, 1),
+			(W, B, 0, 8), (Z, B, 3, 1), (Z, B, 4, 1), (X, R, 0, 6), (Y, G, 0, 4), (X, G, 0, 5),
+			(Z, B, 0, 1), (Z, G, 0, 4), (X, B, 0, 5), (Z, B, 1, 1), (Y, B, 0, 4), (Y, R, 0, 6),
+			(Z, R, 0, 6),
+		],
+	},
+	// mode 8
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5],
+		fields: &[
+			(W, R, 0, 8), (Z, B, 0, 1), (Y, B, 4, 1), (W, G, 0, 8), (Y, G, 5, 1), (Y, G, 4, 1),
+			(W, B, 0, 8), (Z, G, 5, 1), (Z, B, 4, 1), (X, R, 0, 5), (Z, G, 4, 1), (Y, G, 0, 4),
+			(X, G, 0, 6), (Z, G, 0, 4), (X, B, 0, 5), (Z, B, 1, 1), (Y, B, 0, 4), (Y, R, 0, 5),
+			(Z, B, 2, 1), (Z, R, 0, 5), (Z, B, 3, 1),
+		],
+	},
+	// mode 9
+	ModeInfo {
+		two_regions: true, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6],
+		fields: &[
+			(W, R, 0, 8), (Z, B, 1, 1), (Y, B, 4, 1), (W, G, 0, 8), (Y, B, 5, 1), (Y, G, 4, 1),
+			(W, B, 0, 8), (Z, B, 5, 1), (Z, B, 4, 1), (X, R, 0, 5), (Z, G, 4, 1), (Y, G, 0, 4),
+			(X, G, 0, 5), (Z, B, 0, 1), (Z, G, 0, 4), (X, B, 0, 6), (Y, B, 0, 4), (Y, R, 0, 5),
+			(Z, B, 2, 1), (Z, R, 0, 5), (Z, B, 3, 1),
+		],
+	},
+	// mode 10
+	ModeInfo {
+		two_regions: true, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6],
+		fields: &[
+			(W, R, 0, 6), (Z, G, 4, 1), (Z, B, 0, 1), (Z, B, 1, 1), (Y, B, 4, 1), (W, G, 0, 6),
+			(Y, G, 5, 1), (Y, B, 5, 1), (Z, B, 2, 1), (Y, G, 4, 1), (W, B, 0, 6), (Z, G, 5, 1),
+			(Z, B, 3, 1), (Z, B, 5, 1), (Z, B, 4, 1), (X, R, 0, 6), (Y, G, 0, 4), (X, G, 0, 6),
+			(Z, G, 0, 4), (X, B, 0, 6), (Y, B, 0, 4), (Y, R, 0, 6), (Z, R, 0, 6),
+		],
+	},
+	// mode 11
+	ModeInfo {
+		two_regions: false, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10],
+		fields: &[
+			(W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10), (X, R, 0, 10), (X, G, 0, 10), (X, B, 0, 10),
+		],
+	},
+	// mode 12
+	ModeInfo {
+		two_regions: false, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9],
+		fields: &[
+			(W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10), (X, R, 0, 9), (W, R, 10, 1), (X, G, 0, 9),
+			(W, G, 10, 1), (X, B, 0, 9), (W, B, 10, 1),
+		],
+	},
+	// mode 13
+	ModeInfo {
+		two_regions: false, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8],
+		fields: &[
+			(W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10), (X, R, 0, 8), (W, R, 11, 1), (W, R, 10, 1),
+			(X, G, 0, 8), (W, G, 11, 1), (W, G, 10, 1), (X, B, 0, 8), (W, B, 11, 1), (W, B, 10, 1),
+		],
+	},
+	// mode 14
+	ModeInfo {
+		two_regions: false, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4],
+		fields: &[
+			(W, R, 0, 10), (W, G, 0, 10), (W, B, 0, 10),
+			(X, R, 0, 4), (W, R, 15, 1), (W, R, 14, 1), (W, R, 13, 1), (W, R, 12, 1), (W, R, 11, 1), (W, R, 10, 1),
+			(X, G, 0, 4), (W, G, 15, 1), (W, G, 14, 1), (W, G, 13, 1), (W, G, 12, 1), (W, G, 11, 1), (W, G, 10, 1),
+			(X, B, 0, 4), (W, B, 15, 1), (W, B, 14, 1), (W, B, 13, 1), (W, B, 12, 1), (W, B, 11, 1), (W, B, 10, 1),
+		],
+	},
+];
+
+/// Decodes a single BC6H block to RGB half floats.
+///
+/// Blocks with a reserved mode decode to black, as required by the specification.
+pub fn decode(data: &[u8], signed: bool) -> HalfBlock {
+	debug_assert_eq!(data.len(), 16);
+
+	let mut bits = BitReader::new(data);
+
+	// modes 1 and 2 use 2 bits, the others use 5
+	let mode_bits = bits.read(2);
+	let mode_index = match mode_bits {
+		0b00 => 0,
+		0b01 => 1,
+		_ => match mode_bits | (bits.read(3) << 2) {
+			0b00010 => 2,
+			0b00110 => 3,
+			0b01010 => 4,
+			0b01110 => 5,
+			0b10010 => 6,
+			0b10110 => 7,
+			0b11010 => 8,
+			0b11110 => 9,
+			0b00011 => 10,
+			0b00111 => 11,
+			0b01011 => 12,
+			0b01111 => 13,
+			_ => return HalfBlock::default(),
+		},
+	};
+	let mode = &MODES[mode_index];
+
+	let mut endpoints = [[0i32; 3]; 4];
+	for &(endpoint, channel, shift, count) in mode.fields {
+		endpoints[endpoint][channel] |= (bits.read(count as usize) as i32) << shift;
+	}
+
+	let partition = if mode.two_regions {
+		bits.read(5) as usize
+	} else {
+		0
+	};
+
+	let endpoint_count = if mode.two_regions { 4 } else { 2 };
+	let endpoint_bits = mode.endpoint_bits;
+
+	if signed {
+		for c in 0..3 {
+			endpoints[W][c] = sign_extend(endpoints[W][c], endpoint_bits);
+		}
+	}
+
+	// deltas are always signed, non-transformed endpoints only when the format is signed
+	if mode.transformed || signed {
+		for e in 1..endpoint_count {
+			for c in 0..3 {
+				endpoints[e][c] = sign_extend(endpoints[e][c], mode.delta_bits[c]);
+			}
+		}
+	}
+
+	if mode.transformed {
+		let mask = (1 << endpoint_bits) - 1;
+		for e in 1..endpoint_count {
+			for c in 0..3 {
+				endpoints[e][c] = (endpoints[W][c] + endpoints[e][c]) & mask;
+				if signed {
+					endpoints[e][c] = sign_extend(endpoints[e][c], endpoint_bits);
+				}
+			}
+		}
+	}
+
+	for e in 0..endpoint_count {
+		for c in 0..3 {
+			endpoints[e][c] = unquantize(endpoints[e][c], endpoint_bits, signed);
+		}
+	}
+
+	let mut ret = HalfBlock::default();
+	for i in 0..16 {
+		let (subset, index_bits, weights): (usize, usize, &[u8]) = if mode.two_regions {
+			let subset = PARTITION2[partition * 16 + i] as usize;
+			let is_anchor = i == 0 || i == TABLE_ANCHOR_INDEX_SECOND_SUBSET[partition] as usize;
+			(subset, if is_anchor { 2 } else { 3 }, &WEIGHTS3)
+		} else {
+			(0, if i == 0 { 3 } else { 4 }, &WEIGHTS4)
+		};
+
+		let weight = weights[bits.read(index_bits) as usize] as i32;
+
+		let x = i & 3;
+		let y = i >> 2;
+		for c in 0..3 {
+			let e0 = endpoints[subset * 2][c];
+			let e1 = endpoints[subset * 2 + 1][c];
+			let interpolated = (e0 * (64 - weight) + e1 * weight + 32) >> 6;
+			ret[y][x][c] = finish_unquantize(interpolated, signed);
+		}
+	}
+
+	debug_assert_eq!(bits.offset, 128);
+
+	ret
+}
+
+fn sign_extend(value: i32, bits: u8) -> i32 {
+	let shift = 32 - bits as u32;
+	(value << shift) >> shift
+}
+
+/// Scales an endpoint up to 16 bits.
+fn unquantize(value: i32, bits: u8, signed: bool) -> i32 {
+	if !signed {
+		if bits >= 15 || value == 0 {
+			value
+		} else if value == (1 << bits) - 1 {
+			0xFFFF
+		} else {
+			((value << 16) + 0x8000) >> bits
+		}
+	} else {
+		if bits >= 16 || value == 0 {
+			return value;
+		}
+
+		let magnitude = value.abs();
+		let unquantized = if magnitude >= (1 << (bits - 1)) - 1 {
+			0x7FFF
+		} else {
+			((magnitude << 15) + 0x4000) >> (bits - 1)
+		};
+
+		if value < 0 {
+			-unquantized
+		} else {
+			unquantized
+		}
+	}
+}
+
+/// Scales an interpolated value to the bits of a half float.
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+	if !signed {
+		// scale the magnitude by 31/64
+		((value * 31) >> 6) as u16
+	} else {
+		// scale the magnitude by 31/32, and convert to sign-magnitude
+		if value < 0 {
+			0x8000 | (((-value) * 31) >> 5) as u16
+		} else {
+			((value * 31) >> 5) as u16
+		}
+	}
+}
+
+/// Reads bits from a 128-bit block, starting at the least significant bit.
+struct BitReader {
+	data: u128,
+	offset: usize,
+}
+
+impl BitReader {
+	fn new(data: &[u8]) -> Self {
+		Self {
+			data: u128::from_le_bytes(data.try_into().expect("data is not 16 bytes")),
+			offset: 0,
+		}
+	}
+
+	fn read(&mut self, count: usize) -> u32 {
+		debug_assert!(count <= 32);
+		debug_assert!(self.offset + count <= 128);
+		let value = (self.data >> self.offset) as u32 & ((1u64 << count) - 1) as u32;
+		self.offset += count;
+		value
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Writes bits into a 128-bit block, starting at the least significant bit.
+	struct BitWriter {
+		data: u128,
+		offset: usize,
+	}
+
+	impl BitWriter {
+		fn new() -> Self {
+			Self { data: 0, offset: 0 }
+		}
+
+		fn write(&mut self, value: u32, count: usize) -> &mut Self {
+			self.data |= ((value as u128) & ((1 << count) - 1)) << self.offset;
+			self.offset += count;
+			self
+		}
+
+		fn finish(&self) -> [u8; 16] {
+			assert_eq!(self.offset, 128);
+			self.data.to_le_bytes()
+		}
+	}
+
+	/// Builds a mode 11 block, which stores both endpoints as plain 10-bit values.
+	fn mode_11_block(e0: [u32; 3], e1: [u32; 3], indices: [u32; 16]) -> [u8; 16] {
+		let mut writer = BitWriter::new();
+		writer.write(0b00011, 5);
+		for c in e0.iter().chain(e1.iter()) {
+			writer.write(*c, 10);
+		}
+		for (i, index) in indices.iter().enumerate() {
+			writer.write(*index, if i == 0 { 3 } else { 4 });
+		}
+		writer.finish()
+	}
+
+	#[test]
+	fn mode_11_unsigned_endpoints() {
+		let mut indices = [0; 16];
+		indices[15] = 15;
+		let block = mode_11_block([0x3FF, 0, 0x200], [0, 0x3FF, 0x200], indices);
+		let decoded = decode(&block, false);
+
+		// the maximum value maps to the largest finite half float, 65504
+		assert_eq!(decoded[0][0], [0x7BFF, 0x0000, 0x3E0F]);
+		assert_eq!(decoded[3][3], [0x0000, 0x7BFF, 0x3E0F]);
+	}
+
+	#[test]
+	fn mode_11_signed_endpoints() {
+		// -1 as a 10-bit two's complement value
+		let block = mode_11_block([0x3FF, 0x1FF, 0], [0; 3], [0; 16]);
+		let decoded = decode(&block, true);
+
+		// -1 unquantizes to -96, which becomes -(96 * 31 >> 5) = -93. the largest positive value
+		// unquantizes to 0x7FFF, which becomes 65504
+		assert_eq!(decoded[0][0], [0x8000 | 93, 0x7BFF, 0]);
+	}
+
+	#[test]
+	fn mode_14_reversed_endpoint_bits() {
+		// w is stored as 10 normal bits followed by the 6 top bits in reverse order
+		let mut writer = BitWriter::new();
+		writer.write(0b01111, 5);
+		writer.write(0x001, 10).write(0x001, 10).write(0x001, 10);
+		for _ in 0..3 {
+			// x = 0, followed by bits 15 to 10 of w: only bit 15 is set
+			writer.write(0, 4).write(1, 1).write(0, 5);
+		}
+		writer.write(0, 63);
+		let decoded = decode(&writer.finish(), false);
+
+		// 16-bit endpoints are not unquantized: (0x8001 * 31) >> 6
+		let expected = ((0x8001 * 31) >> 6) as u16;
+		assert_eq!(decoded[1][2], [expected; 3]);
+	}
+
+	#[test]
+	fn mode_10_uses_partitions() {
+		// mode 10 stores all endpoints as plain 6-bit values. use partition 0 (left and right half)
+		// with subset 0 going from black to black and subset 1 from white to white
+		let mut writer = BitWriter::new();
+		writer.write(0b11110, 5);
+		let mut fields = [[0u32; 3]; 4];
+		fields[Y] = [0x3F; 3];
+		fields[Z] = [0x3F; 3];
+		for &(endpoint, channel, shift, count) in MODES[9].fields {
+			writer.write(fields[endpoint][channel] >> shift, count as usize);
+		}
+		writer.write(0, 5).write(0, 46);
+		let decoded = decode(&writer.finish(), false);
+
+		for y in 0..4 {
+			assert_eq!(decoded[y][0], [0; 3]);
+			assert_eq!(decoded[y][3], [0x7BFF; 3]);
+		}
+	}
+
+	#[test]
+	fn reserved_mode_is_black() {
+		let mut block = [0xFFu8; 16];
+		block[0] = 0b10011;
+		assert_eq!(decode(&block, false), HalfBlock::default());
+	}
+
+	#[test]
+	fn all_modes_use_128_bits() {
+		for (i, mode) in MODES.iter().enumerate() {
+			let mode_bits = if i < 2 { 2 } else { 5 };
+			let header_bits = mode
+				.fields
+				.iter()
+				.map(|&(_, _, _, count)| count as usize)
+				.sum::<usize>();
+			let (partition_bits, index_bits) = if mode.two_regions { (5, 46) } else { (0, 63) };
+			assert_eq!(
+				mode_bits + header_bits + partition_bits + index_bits,
+				128,
+				"mode {}",
+				i + 1
+			);
+		}
+	}
+}
@@ -0,0 +1,175 @@
+mod blocks;
+
+use std::convert::Infallible;
+
+use crate::{util::decode_blocks, Color4, ColorBlock};
+
+/// Decodes a BC6H image to RGB half floats, stored as their raw bits. Each pixel takes 3 values.
+pub fn read_image_f16(data: &[u8], width: usize, height: usize, signed: bool) -> Vec<u16> {
+	let blocks_x = usize::max(1, width.div_ceil(4));
+	let blocks_y = usize::max(1, height.div_ceil(4));
+	let block_count = blocks_x * blocks_y;
+	let decoded_pixel_count = block_count * 16;
+
+	// each 16 byte chunk is a 4x4 block of rgb half floats
+	let mut decoded_pixels = vec![[0u16; 3]; decoded_pixel_count];
+	for (chunk_index, chunk) in data.chunks_exact(16).take(block_count).enumerate() {
+		let chunk = blocks::decode(chunk, signed);
+
+		// copy the chunk into the decoded pixels
+		let chunk_x = (chunk_index % blocks_x) * 4;
+		let chunk_y = (chunk_index / blocks_x) * 4;
+		let target_index = chunk_y * (blocks_x * 4) + chunk_x;
+
+		#[allow(clippy::needless_range_loop)]
+		for y in 0..4 {
+			for x in 0..4 {
+				let target_index = target_index + y * (blocks_x * 4) + x;
+				decoded_pixels[target_index] = chunk[y][x];
+			}
+		}
+	}
+
+	// the decoded pixels may contain some "padding" on each row, since the width and height may not
+	// be divisible by the decoded block size
+	let mut final_pixels = Vec::with_capacity(width * height * 3);
+	for line in decoded_pixels.chunks_exact(blocks_x * 4).take(height) {
+		final_pixels.extend(line[0..width].iter().flatten());
+	}
+
+	tracing::debug!("image decoded");
+
+	final_pixels
+}
+
+/// Decodes a BC6H image to RGB 32-bit floats. Each pixel takes 3 values.
+pub fn read_image_f32(data: &[u8], width: usize, height: usize, signed: bool) -> Vec<f32> {
+	read_image_f16(data, width, height, signed)
+		.into_iter()
+		.map(half_to_f32)
+		.collect()
+}
+
+/// Decodes a BC6H image to RGBA8, tone mapping the HDR colors to the displayable range.
+pub fn read_image(
+	data: &[u8],
+	width: usize,
+	height: usize,
+	signed: bool,
+	output: &mut [u8],
+	stride: usize,
+) {
+	// BC6H has a chunk size of 16 bytes, each block is tone mapped straight into the output
+	let Ok(()) =
+		decode_blocks::<Infallible, _>(data, width, height, 16, output, stride, |_, chunk| {
+			Ok(decode_block(chunk, signed))
+		});
+
+	tracing::debug!("image decoded");
+}
+
+/// Decodes a single BC6H block to RGBA8, tone mapping the HDR colors to the displayable range.
+pub fn decode_block(block: &[u8], signed: bool) -> ColorBlock {
+	blocks::decode(block, signed).map(|row| {
+		row.map(|color| Color4 {
+			components: [
+				tone_map(half_to_f32(color[0])),
+				tone_map(half_to_f32(color[1])),
+				tone_map(half_to_f32(color[2])),
+				255,
+			],
+		})
+	})
+}
+
+/// Converts the bits of a half float to a 32-bit float.
+pub fn half_to_f32(half: u16) -> f32 {
+	let sign = (half as u32 & 0x8000) << 16;
+	let exponent = (half >> 10) & 0x1F;
+	let mantissa = half as u32 & 0x3FF;
+
+	let bits = match exponent {
+		// zero or subnormal, which are normal numbers when stored as f32
+		0 => {
+			if mantissa == 0 {
+				sign
+			} else {
+				let shift = mantissa.leading_zeros() - 21;
+				let mantissa = (mantissa << shift) & 0x3FF;
+				let exponent = 127 - 15 + 1 - shift;
+				sign | (exponent << 23) | (mantissa << 13)
+			}
+		}
+		// infinity or NaN
+		0x1F => sign | 0x7F80_0000 | (mantissa << 13),
+		_ => sign | ((exponent as u32 + 127 - 15) << 23) | (mantissa << 13),
+	};
+
+	f32::from_bits(bits)
+}
+
+/// Maps a linear HDR value to an sRGB byte, using the Reinhard operator. Negative values are
+/// clamped to black.
+fn tone_map(value: f32) -> u8 {
+	if value.is_nan() || value <= 0.0 {
+		return 0;
+	}
+
+	let linear = if value.is_infinite() {
+		1.0
+	} else {
+		value / (1.0 + value)
+	};
+	let srgb = if linear <= 0.003_130_8 {
+		linear * 12.92
+	} else {
+		1.055 * linear.powf(1.0 / 2.4) - 0.055
+	};
+
+	(srgb * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn half_to_f32_converts_correctly() {
+		assert_eq!(half_to_f32(0x0000), 0.0);
+		assert_eq!(half_to_f32(0x3C00), 1.0);
+		assert_eq!(half_to_f32(0xC000), -2.0);
+		assert_eq!(half_to_f32(0x7BFF), 65504.0);
+		assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+		assert_eq!(half_to_f32(0x03FF), 1023.0 * 2f32.powi(-24));
+		assert_eq!(half_to_f32(0x7C00), f32::INFINITY);
+		assert!(half_to_f32(0x7E00).is_nan());
+	}
+
+	#[test]
+	fn tone_map_clamps_range() {
+		assert_eq!(tone_map(-1.0), 0);
+		assert_eq!(tone_map(0.0), 0);
+		assert_eq!(tone_map(f32::INFINITY), 255);
+		// 1.0 maps to 0.5 linear, which is 188 in sRGB
+		assert_eq!(tone_map(1.0), 188);
+	}
+
+	#[test]
+	fn read_image_crops_padding() {
+		// a mode 11 block with endpoints of 0 and 0x3FF, and all indices set to 0
+		let mut block = [0u8; 16];
+		block[0] = 0b1110_0011;
+		block[1] = 0xFF;
+		block[2] = 0xFF;
+		block[3] = 0xFF;
+		block[4] = 0x07;
+
+		let f16 = read_image_f16(&block, 3, 2, false);
+		assert_eq!(f16.len(), 3 * 2 * 3);
+		assert!(f16.chunks_exact(3).all(|p| p == [0x7BFF, 0x7BFF, 0x7BFF]));
+
+		let mut rgba = [0; 3 * 2 * 4];
+		read_image(&block, 3, 2, false, &mut rgba, 3 * 4);
+		assert!(rgba.chunks_exact(4).all(|p| p == [255, 255, 255, 255]));
+	}
+}
@@ -9,7 +9,7 @@
 use crate::{errors::Bc7Error, Color4, ColorBlock};
 
 #[rustfmt::skip]
-const PARTITION2: [u8; 64 * 16] =
+pub(crate) const PARTITION2: [u8; 64 * 16] =
 [
 	0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1, 0,0,0,1,0,0,0,1,0,0,0,1,0,0,0,1, 0,1,1,1,0,1,1,1,0,1,1,1,0,1,1,1, 0,0,0,1,0,0,1,1,0,0,1,1,0,1,1,1, 0,0,0,0,0,0,0,1,0,0,0,1,0,0,1,1, 0,0,1,1,0,1,1,1,0,1,1,1,1,1,1,1, 0,0,0,1,0,0,1,1,0,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,1,0,0,1,1,0,1,1,1,
 	0,0,0,0,0,0,0,0,0,0,0,1,0,0,1,1, 0,0,1,1,0,1,1,1,1,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,1,0,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,0,0,0,0,1,0,1,1,1, 0,0,0,1,0,1,1,1,1,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,0,1,1,1,1,1,1,1,1, 0,0,0,0,1,1,1,1,1,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,0,0,0,0,0,1,1,1,1,
@@ -35,7 +35,7 @@ const PARTITION3: [u8; 64 * 16] =
 ];
 
 #[rustfmt::skip]
-const TABLE_ANCHOR_INDEX_SECOND_SUBSET: [u8; 64] = [
+pub(crate) const TABLE_ANCHOR_INDEX_SECOND_SUBSET: [u8; 64] = [
 	15,15,15,15,15,15,15,15,
 	15,15,15,15,15,15,15,15,
 	15, 2, 8, 2, 2, 8, 8,15,
@@ -71,8 +71,8 @@ const TABLE_ANCHOR_INDEX_THIRD_SUBSET_2: [u8; 64] = [
 ];
 
 const WEIGHTS2: [u8; 4] = [0, 21, 43, 64];
-const WEIGHTS3: [u8; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
-const WEIGHTS4: [u8; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+pub(crate) const WEIGHTS3: [u8; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+pub(crate) const WEIGHTS4: [u8; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
 
 pub fn decode(data: &[u8]) -> Result<ColorBlock, Bc7Error> {
 	debug_assert_eq!(data.len(), 16);
@@ -474,7 +474,7 @@ fn bc7_dequant(val: u8, val_bits: usize) -> u8 {
 	val as u8
 }
 
-fn bc7_dequant_with_pbit(val: u8, pbit: usize, val_bits: usize) -> u8 {
+pub(crate) fn bc7_dequant_with_pbit(val: u8, pbit: usize, val_bits: usize) -> u8 {
 	let val = val as usize;
 
 	debug_assert!(val < (1 << val_bits));
@@ -511,7 +511,7 @@ fn bc7_interp4(l: u8, h: u8, w: usize) -> u8 {
 	((l * (64 - WEIGHTS4[w]) as usize + h * WEIGHTS4[w] as usize + 32) >> 6) as u8
 }
 
-fn bc7_interp(l: u8, h: u8, w: usize, bits: usize) -> u8 {
+pub(crate) fn bc7_interp(l: u8, h: u8, w: usize, bits: usize) -> u8 {
 	match bits {
 		2 => bc7_interp2(l, h, w),
 		3 => bc7_interp3(l, h, w),
@@ -0,0 +1,235 @@
+#![allow(clippy::needless_range_loop)]
+
+use super::blocks::{
+	bc7_dequant_with_pbit, bc7_interp, PARTITION2, TABLE_ANCHOR_INDEX_SECOND_SUBSET,
+};
+use crate::{
+	util::{closest_color, range_fit},
+	Color4,
+};
+
+/// Encodes a 4x4 block of RGBA8 pixels to BC7.
+///
+/// Only modes 1 and 6 are used. Mode 6 stores the whole block with a single pair of RGBA
+/// endpoints, while mode 1 splits opaque blocks into 2 subsets that each have their own RGB
+/// endpoints. The mode with the smallest error is picked.
+pub fn encode_block(pixels: &[[u8; 4]; 16], output: &mut [u8]) {
+	debug_assert_eq!(output.len(), 16);
+
+	let mut best = encode_mode_6(pixels);
+	if pixels.iter().all(|pixel| pixel[3] == 255) {
+		let mode_1 = (0..64)
+			.map(|partition| encode_mode_1(pixels, partition))
+			.min_by_key(|block| block.error)
+			.expect("there are 64 partitions");
+		if mode_1.error < best.error {
+			best = mode_1;
+		}
+	}
+
+	output.copy_from_slice(&best.data.to_le_bytes());
+}
+
+struct EncodedBlock {
+	data: u128,
+	/// The sum of the squared errors of every channel of every pixel.
+	error: u32,
+}
+
+fn encode_mode_6(pixels: &[[u8; 4]; 16]) -> EncodedBlock {
+	const ENDPOINT_BITS: usize = 7;
+	const WEIGHT_BITS: usize = 4;
+
+	let (start, end) = range_fit::<4>(pixels);
+	let ([mut endpoint_0], mut pbit_0) = quantize_endpoints([start], ENDPOINT_BITS, 4);
+	let ([mut endpoint_1], mut pbit_1) = quantize_endpoints([end], ENDPOINT_BITS, 4);
+	let palette = palette(
+		dequantize(endpoint_0, pbit_0, ENDPOINT_BITS, 4),
+		dequantize(endpoint_1, pbit_1, ENDPOINT_BITS, 4),
+		WEIGHT_BITS,
+	);
+
+	let mut error = 0;
+	let mut weights = [0usize; 16];
+	for (weight, pixel) in weights.iter_mut().zip(pixels) {
+		let (index, pixel_error) = closest_color(&palette, pixel, 4);
+		*weight = index;
+		error += pixel_error;
+	}
+
+	// the highest bit of the first weight is implied to be 0, swapping the endpoints makes sure
+	// that is the case. the weights are symmetric, so this doesn't change the palette.
+	if weights[0] >= 8 {
+		std::mem::swap(&mut endpoint_0, &mut endpoint_1);
+		std::mem::swap(&mut pbit_0, &mut pbit_1);
+		weights = weights.map(|weight| 15 - weight);
+	}
+
+	let mut writer = BitWriter::default();
+	writer.write(1 << 6, 7);
+	for c in 0..4 {
+		writer.write(endpoint_0[c] as usize, ENDPOINT_BITS);
+		writer.write(endpoint_1[c] as usize, ENDPOINT_BITS);
+	}
+	writer.write(pbit_0, 1);
+	writer.write(pbit_1, 1);
+	for (i, &weight) in weights.iter().enumerate() {
+		let bits = if i == 0 { WEIGHT_BITS - 1 } else { WEIGHT_BITS };
+		writer.write(weight, bits);
+	}
+
+	EncodedBlock {
+		data: writer.finish(),
+		error,
+	}
+}
+
+fn encode_mode_1(pixels: &[[u8; 4]; 16], partition: usize) -> EncodedBlock {
+	const ENDPOINT_BITS: usize = 6;
+	const WEIGHT_BITS: usize = 3;
+
+	let subsets = &PARTITION2[partition * 16..(partition + 1) * 16];
+	let anchors = [0, TABLE_ANCHOR_INDEX_SECOND_SUBSET[partition] as usize];
+
+	let mut endpoints = [[[0u8; 4]; 2]; 2];
+	let mut pbits = [0usize; 2];
+	let mut weights = [0usize; 16];
+	let mut error = 0;
+	for subset in 0..2 {
+		let in_subset = |i: &usize| subsets[*i] as usize == subset;
+		let subset_pixels = (0..16)
+			.filter(in_subset)
+			.map(|i| pixels[i])
+			.collect::<Vec<_>>();
+
+		let (start, end) = range_fit::<3>(&subset_pixels);
+		let with_alpha = |color: [f32; 3]| [color[0], color[1], color[2], 255.0];
+		let (mut quantized, pbit) =
+			quantize_endpoints([with_alpha(start), with_alpha(end)], ENDPOINT_BITS, 3);
+		let palette = palette(
+			dequantize(quantized[0], pbit, ENDPOINT_BITS, 3),
+			dequantize(quantized[1], pbit, ENDPOINT_BITS, 3),
+			WEIGHT_BITS,
+		);
+
+		for i in (0..16).filter(in_subset) {
+			let (index, pixel_error) = closest_color(&palette, &pixels[i], 3);
+			weights[i] = index;
+			error += pixel_error;
+		}
+
+		// same as mode 6, but for the anchor of each subset. the p-bit is shared by both endpoints.
+		if weights[anchors[subset]] >= 4 {
+			quantized.swap(0, 1);
+			for i in (0..16).filter(in_subset) {
+				weights[i] = 7 - weights[i];
+			}
+		}
+
+		endpoints[subset] = quantized;
+		pbits[subset] = pbit;
+	}
+
+	let mut writer = BitWriter::default();
+	writer.write(1 << 1, 2);
+	writer.write(partition, 6);
+	for c in 0..3 {
+		for subset_endpoints in &endpoints {
+			for endpoint in subset_endpoints {
+				writer.write(endpoint[c] as usize, ENDPOINT_BITS);
+			}
+		}
+	}
+	for pbit in pbits {
+		writer.write(pbit, 1);
+	}
+	for (i, &weight) in weights.iter().enumerate() {
+		let bits = if anchors.contains(&i) {
+			WEIGHT_BITS - 1
+		} else {
+			WEIGHT_BITS
+		};
+		writer.write(weight, bits);
+	}
+
+	EncodedBlock {
+		data: writer.finish(),
+		error,
+	}
+}
+
+/// Quantizes endpoints that share a p-bit to `bits` bits for the first `channels` channels,
+/// picking the p-bit that keeps them closest to the original values.
+fn quantize_endpoints<const E: usize>(
+	endpoints: [[f32; 4]; E],
+	bits: usize,
+	channels: usize,
+) -> ([[u8; 4]; E], usize) {
+	let max_value = ((1 << bits) - 1) as f32;
+	let scale = ((1 << (bits + 1)) - 1) as f32 / 255.0;
+
+	let quantize = |pbit: usize| {
+		let mut quantized = [[0u8; 4]; E];
+		let mut error = 0f32;
+		for (endpoint, quantized) in endpoints.iter().zip(quantized.iter_mut()) {
+			for c in 0..channels {
+				let value = (endpoint[c] * scale - pbit as f32) / 2.0;
+				quantized[c] = value.round().clamp(0.0, max_value) as u8;
+
+				let difference =
+					bc7_dequant_with_pbit(quantized[c], pbit, bits) as f32 - endpoint[c];
+				error += difference * difference;
+			}
+		}
+		(quantized, error)
+	};
+
+	let (quantized_0, error_0) = quantize(0);
+	let (quantized_1, error_1) = quantize(1);
+	if error_0 <= error_1 {
+		(quantized_0, 0)
+	} else {
+		(quantized_1, 1)
+	}
+}
+
+/// Restores a quantized endpoint to RGBA8. Channels past `channels` are opaque.
+fn dequantize(endpoint: [u8; 4], pbit: usize, bits: usize, channels: usize) -> Color4 {
+	let mut color = Color4 {
+		components: [255; 4],
+	};
+	for c in 0..channels {
+		color.components[c] = bc7_dequant_with_pbit(endpoint[c], pbit, bits);
+	}
+	color
+}
+
+/// Builds the palette of `1 << weight_bits` colors between 2 endpoints.
+fn palette(start: Color4, end: Color4, weight_bits: usize) -> Vec<Color4> {
+	(0..1 << weight_bits)
+		.map(|weight| Color4 {
+			components: [0, 1, 2, 3]
+				.map(|c| bc7_interp(start.components[c], end.components[c], weight, weight_bits)),
+		})
+		.collect()
+}
+
+/// Writes values into a 128-bit block, starting at the lowest bit.
+#[derive(Default)]
+struct BitWriter {
+	data: u128,
+	bit_offset: usize,
+}
+
+impl BitWriter {
+	fn write(&mut self, value: usize, bits: usize) {
+		debug_assert!(value < (1 << bits));
+		self.data |= (value as u128) << self.bit_offset;
+		self.bit_offset += bits;
+	}
+
+	fn finish(self) -> u128 {
+		debug_assert_eq!(self.bit_offset, 128);
+		self.data
+	}
+}
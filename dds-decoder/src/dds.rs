@@ -0,0 +1,840 @@
+//! Parsing and writing of `.dds` files, including the DX10 extension header.
+//!
+//! See: <https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide>
+
+use std::io::Write;
+
+use crate::{
+	decode_image,
+	errors::{DdsDecodeError, DdsFileError, DdsWriteError},
+	DdsFormat,
+};
+
+pub(crate) const MAGIC: [u8; 4] = *b"DDS ";
+pub(crate) const HEADER_SIZE: u32 = 124;
+pub(crate) const PIXEL_FORMAT_SIZE: u32 = 32;
+pub(crate) const DX10_HEADER_SIZE: usize = 20;
+
+/// Pixel format flag: the four_cc field is set.
+pub(crate) const DDPF_FOURCC: u32 = 0x4;
+/// Pixel format flag: the texture contains uncompressed RGB data.
+pub(crate) const DDPF_RGB: u32 = 0x40;
+/// Pixel format flag: the texture contains alpha data.
+pub(crate) const DDPF_ALPHAPIXELS: u32 = 0x1;
+/// Pixel format flag: the texture only contains alpha data.
+pub(crate) const DDPF_ALPHA: u32 = 0x2;
+/// Pixel format flag: the texture contains luminance data.
+pub(crate) const DDPF_LUMINANCE: u32 = 0x20000;
+
+/// Header flags that are required in every file: caps, height, width and pixel format.
+pub(crate) const DDSD_REQUIRED: u32 = 0x1 | 0x2 | 0x4 | 0x1000;
+/// Header flag: the pitch field contains the size of a row of an uncompressed texture.
+pub(crate) const DDSD_PITCH: u32 = 0x8;
+/// Header flag: the mipmap count field is set.
+pub(crate) const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+/// Header flag: the pitch field contains the size of the top level of a compressed texture.
+pub(crate) const DDSD_LINEARSIZE: u32 = 0x80000;
+
+/// Caps flag: required in every file.
+pub(crate) const DDSCAPS_TEXTURE: u32 = 0x1000;
+/// Caps flag: the file contains more than one surface.
+pub(crate) const DDSCAPS_COMPLEX: u32 = 0x8;
+/// Caps flag: the file contains mipmaps.
+pub(crate) const DDSCAPS_MIPMAP: u32 = 0x40_0000;
+
+/// Caps2 flag: the texture is a cubemap.
+pub(crate) const DDSCAPS2_CUBEMAP: u32 = 0x200;
+/// Caps2 flags: all 6 faces of a cubemap are present.
+pub(crate) const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFC00;
+/// DX10 resource dimension of a 2D texture.
+pub(crate) const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+/// DX10 misc flag: the texture is a cubemap.
+pub(crate) const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// A parsed `.dds` file, borrowing its surface data from the input.
+pub struct DdsFile<'a> {
+	pub description: DdsDescription,
+	data: &'a [u8],
+}
+
+/// The layout of the surfaces in a `.dds` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DdsDescription {
+	pub width: u32,
+	pub height: u32,
+	/// The depth of a volume texture, or 1 for other textures.
+	pub depth: u32,
+	/// The amount of mipmap levels, including the full-size image. Always at least 1.
+	pub mipmap_count: u32,
+	/// The amount of textures in a texture array, or the amount of cubemaps in a cubemap array.
+	/// Always at least 1.
+	pub array_size: u32,
+	pub is_cubemap: bool,
+	pub format: DdsFormat,
+	/// The FourCC code of the pixel format, if any. This is `DX10` when the DX10 header is present.
+	pub four_cc: Option<[u8; 4]>,
+	/// The `DXGI_FORMAT` from the DX10 header, if present.
+	pub dxgi_format: Option<u32>,
+}
+
+/// A single decoded RGBA8 surface of a `.dds` file.
+pub struct DdsSurface {
+	/// The index of this surface in the texture array. For cubemaps, this is the face index plus 6
+	/// times the cubemap index.
+	pub layer: u32,
+	/// The mipmap level, where 0 is the full-size image.
+	pub level: u32,
+	pub width: u32,
+	pub height: u32,
+	pub data: Vec<u8>,
+}
+
+impl<'a> DdsFile<'a> {
+	/// Parses the headers of a `.dds` file.
+	pub fn parse(data: &'a [u8]) -> Result<Self, DdsDecodeError> {
+		if data.len() < 4 + HEADER_SIZE as usize || data[0..4] != MAGIC {
+			return Err(DdsFileError::InvalidMagic.into());
+		}
+
+		let read_u32 = |offset: usize| {
+			data.get(offset..offset + 4)
+				.map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+				.ok_or(DdsFileError::UnexpectedEndOfFile)
+		};
+
+		let header_size = read_u32(4)?;
+		if header_size != HEADER_SIZE {
+			return Err(DdsFileError::InvalidHeaderSize(header_size).into());
+		}
+
+		let height = read_u32(12)?;
+		let width = read_u32(16)?;
+		let depth = u32::max(1, read_u32(24)?);
+		let mipmap_count = u32::max(1, read_u32(28)?);
+
+		let pixel_format_size = read_u32(76)?;
+		if pixel_format_size != PIXEL_FORMAT_SIZE {
+			return Err(DdsFileError::InvalidPixelFormatSize(pixel_format_size).into());
+		}
+		let pixel_format_flags = read_u32(80)?;
+		let four_cc = (pixel_format_flags & DDPF_FOURCC != 0).then(|| {
+			let mut four_cc = [0u8; 4];
+			four_cc.copy_from_slice(&data[84..88]);
+			four_cc
+		});
+		let caps2 = read_u32(112)?;
+
+		let mut data_offset = 4 + HEADER_SIZE as usize;
+		let (format, dxgi_format, array_size, is_cubemap) = if four_cc == Some(*b"DX10") {
+			let dxgi_format = read_u32(data_offset)?;
+			let misc_flag = read_u32(data_offset + 8)?;
+			let array_size = u32::max(1, read_u32(data_offset + 12)?);
+			data_offset += DX10_HEADER_SIZE;
+
+			let format = format_from_dxgi(dxgi_format)
+				.ok_or(DdsFileError::UnsupportedDxgiFormat(dxgi_format))?;
+			let is_cubemap = misc_flag & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+			(format, Some(dxgi_format), array_size, is_cubemap)
+		} else if let Some(four_cc) = four_cc {
+			let format =
+				format_from_four_cc(four_cc).ok_or(DdsFileError::UnsupportedFourCc(four_cc))?;
+			(format, None, 1, caps2 & DDSCAPS2_CUBEMAP != 0)
+		} else {
+			let format = format_from_masks(
+				pixel_format_flags,
+				read_u32(88)?,
+				[read_u32(92)?, read_u32(96)?, read_u32(100)?, read_u32(104)?],
+			)
+			.ok_or(DdsFileError::UnsupportedPixelFormat(pixel_format_flags))?;
+			(format, None, 1, caps2 & DDSCAPS2_CUBEMAP != 0)
+		};
+
+		if depth > 1 {
+			return Err(DdsFileError::VolumeTexture(depth).into());
+		}
+
+		if is_cubemap
+			&& four_cc != Some(*b"DX10")
+			&& caps2 & DDSCAPS2_CUBEMAP_ALL_FACES != DDSCAPS2_CUBEMAP_ALL_FACES
+		{
+			return Err(DdsFileError::PartialCubemap.into());
+		}
+
+		// every level halves the size, until both dimensions are 1
+		let max_mipmap_count = 32 - u32::max(1, u32::max(width, height)).leading_zeros();
+		if mipmap_count > max_mipmap_count {
+			return Err(DdsFileError::InvalidMipmapCount(
+				mipmap_count,
+				width,
+				height,
+				max_mipmap_count,
+			)
+			.into());
+		}
+
+		// every layer takes at least 1 byte, so this bounds the amount of layers without
+		// computing their size. a single layer is always allowed, so a header can be parsed on its
+		// own.
+		let max_layer_count = usize::max(1, data.len().saturating_sub(data_offset));
+		let faces = if is_cubemap { 6 } else { 1 };
+		let layer_count = array_size.checked_mul(faces);
+		if layer_count.is_none_or(|layer_count| layer_count as usize > max_layer_count) {
+			return Err(DdsFileError::InvalidArraySize(array_size, max_layer_count).into());
+		}
+
+		let description = DdsDescription {
+			width,
+			height,
+			depth,
+			mipmap_count,
+			array_size,
+			is_cubemap,
+			format,
+			four_cc,
+			dxgi_format,
+		};
+		tracing::debug!(?description, "parsed dds header");
+
+		Ok(Self {
+			description,
+			data: &data[data_offset..],
+		})
+	}
+
+	/// Returns the raw data of a single surface.
+	pub fn surface_data(&self, layer: u32, level: u32) -> Result<&'a [u8], DdsDecodeError> {
+		let description = &self.description;
+		if layer >= description.layer_count() || level >= description.mipmap_count {
+			return Err(DdsFileError::InvalidSurface(layer, level).into());
+		}
+
+		// the sizes come from the header, so they may not fit in memory
+		let (width, height) = description.mipmap_dimensions(level);
+		let range = || {
+			let offset = (layer as usize)
+				.checked_mul(description.layer_size()?)?
+				.checked_add(description.mipmap_offset(level)?)?;
+			let size = description
+				.format
+				.checked_data_size(width as usize, height as usize)?;
+			Some(offset..offset.checked_add(size)?)
+		};
+
+		range()
+			.and_then(|range| self.data.get(range))
+			.ok_or(DdsFileError::UnexpectedEndOfFile.into())
+	}
+
+	/// Decodes a single surface to RGBA8.
+	pub fn decode_surface(&self, layer: u32, level: u32) -> Result<DdsSurface, DdsDecodeError> {
+		let data = self.surface_data(layer, level)?;
+		let (width, height) = self.description.mipmap_dimensions(level);
+		let data = decode_image(
+			self.description.format,
+			data,
+			width as usize,
+			height as usize,
+		)?;
+
+		Ok(DdsSurface {
+			layer,
+			level,
+			width,
+			height,
+			data,
+		})
+	}
+
+	/// Decodes every surface to RGBA8. The surfaces are ordered by layer first, and then by
+	/// mipmap level, which is the same order as they are stored in.
+	pub fn decode_surfaces(&self) -> Result<Vec<DdsSurface>, DdsDecodeError> {
+		(0..self.description.layer_count())
+			.flat_map(|layer| (0..self.description.mipmap_count).map(move |level| (layer, level)))
+			.map(|(layer, level)| self.decode_surface(layer, level))
+			.collect()
+	}
+}
+
+impl DdsDescription {
+	/// Creates a description for writing a 2D texture, which always uses the DX10 header.
+	pub fn new(
+		format: DdsFormat,
+		width: u32,
+		height: u32,
+		mipmap_count: u32,
+		array_size: u32,
+		is_cubemap: bool,
+	) -> Self {
+		Self {
+			width,
+			height,
+			depth: 1,
+			mipmap_count,
+			array_size,
+			is_cubemap,
+			format,
+			four_cc: Some(*b"DX10"),
+			dxgi_format: dxgi_from_format(format),
+		}
+	}
+
+	/// Returns the amount of 2D images in the file, not counting mipmaps. For cubemaps, this is
+	/// 6 times the array size.
+	pub fn layer_count(&self) -> u32 {
+		if self.is_cubemap {
+			self.array_size * 6
+		} else {
+			self.array_size
+		}
+	}
+
+	/// Returns the width and height of a mipmap level. Level 0 is the full-size image.
+	pub fn mipmap_dimensions(&self, level: u32) -> (u32, u32) {
+		(
+			u32::max(1, self.width.checked_shr(level).unwrap_or(0)),
+			u32::max(1, self.height.checked_shr(level).unwrap_or(0)),
+		)
+	}
+
+	/// Returns the offset of a mipmap level relative to the start of its layer, or `None` if it
+	/// does not fit in a `usize`.
+	fn mipmap_offset(&self, level: u32) -> Option<usize> {
+		(0..level).try_fold(0usize, |offset, level| {
+			let (width, height) = self.mipmap_dimensions(level);
+			let size = self
+				.format
+				.checked_data_size(width as usize, height as usize)?;
+			offset.checked_add(size)
+		})
+	}
+
+	/// Returns the size of a single layer, including all its mipmap levels, or `None` if it does
+	/// not fit in a `usize`.
+	fn layer_size(&self) -> Option<usize> {
+		self.mipmap_offset(self.mipmap_count)
+	}
+}
+
+/// Writes a `.dds` file with a DX10 header.
+///
+/// The data has to contain every surface, in the order described by [DdsFile::decode_surfaces]:
+/// every layer with its full mipmap chain. It is written as-is, so compressed data is not
+/// re-encoded.
+pub fn write_dds(
+	mut writer: impl Write,
+	description: &DdsDescription,
+	data: &[u8],
+) -> Result<(), DdsWriteError> {
+	let format = description.format;
+	let dxgi_format = dxgi_from_format(format).ok_or(DdsWriteError::UnsupportedFormat(format))?;
+
+	let mipmap_count = u32::max(1, description.mipmap_count);
+	let array_size = u32::max(1, description.array_size);
+	let description = DdsDescription {
+		mipmap_count,
+		array_size,
+		..description.clone()
+	};
+
+	// a size that doesn't fit in a usize can't match the data either
+	let expected_size = description
+		.layer_size()
+		.and_then(|layer_size| layer_size.checked_mul(description.layer_count() as usize))
+		.unwrap_or(usize::MAX);
+	if data.len() != expected_size {
+		return Err(DdsWriteError::InvalidDataSize(expected_size, data.len()));
+	}
+
+	let mut flags = DDSD_REQUIRED;
+	let pitch = if format.is_block_compressed() {
+		flags |= DDSD_LINEARSIZE;
+		format.data_size(description.width as usize, description.height as usize)
+	} else {
+		flags |= DDSD_PITCH;
+		description.width as usize * format.block_size()
+	};
+	if mipmap_count > 1 {
+		flags |= DDSD_MIPMAPCOUNT;
+	}
+
+	let mut caps = DDSCAPS_TEXTURE;
+	if mipmap_count > 1 {
+		caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+	}
+	let mut caps2 = 0;
+	let mut misc_flag = 0;
+	if description.is_cubemap {
+		caps |= DDSCAPS_COMPLEX;
+		caps2 |= DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES;
+		misc_flag |= DDS_RESOURCE_MISC_TEXTURECUBE;
+	}
+	if array_size > 1 {
+		caps |= DDSCAPS_COMPLEX;
+	}
+
+	let mut header = [0u32; 31];
+	header[0] = HEADER_SIZE;
+	header[1] = flags;
+	header[2] = description.height;
+	header[3] = description.width;
+	header[4] = pitch as u32;
+	header[6] = mipmap_count;
+	header[18] = PIXEL_FORMAT_SIZE;
+	header[19] = DDPF_FOURCC;
+	header[20] = u32::from_le_bytes(*b"DX10");
+	header[26] = caps;
+	header[27] = caps2;
+
+	let dx10_header = [
+		dxgi_format,
+		DDS_DIMENSION_TEXTURE2D,
+		misc_flag,
+		array_size,
+		0,
+	];
+
+	writer.write_all(&MAGIC)?;
+	for value in header.iter().chain(dx10_header.iter()) {
+		writer.write_all(&value.to_le_bytes())?;
+	}
+	writer.write_all(data)?;
+
+	Ok(())
+}
+
+fn format_from_four_cc(four_cc: [u8; 4]) -> Option<DdsFormat> {
+	match &four_cc {
+		b"DXT1" => Some(DdsFormat::BC1),
+		b"DXT5" => Some(DdsFormat::BC3),
+		b"ATI1" | b"BC4U" => Some(DdsFormat::BC4),
+		b"BC4S" => Some(DdsFormat::BC4Signed),
+		b"ATI2" | b"BC5U" => Some(DdsFormat::BC5),
+		b"BC5S" => Some(DdsFormat::BC5Signed),
+		_ => None,
+	}
+}
+
+fn format_from_dxgi(dxgi_format: u32) -> Option<DdsFormat> {
+	match dxgi_format {
+		// R8G8B8A8_UNORM, R8G8B8A8_UNORM_SRGB
+		28 | 29 => Some(DdsFormat::RGBA8),
+		// R8_UNORM
+		61 => Some(DdsFormat::L8),
+		// A8_UNORM
+		65 => Some(DdsFormat::A8),
+		// BC1_UNORM, BC1_UNORM_SRGB
+		71 | 72 => Some(DdsFormat::BC1),
+		// BC3_UNORM, BC3_UNORM_SRGB
+		77 | 78 => Some(DdsFormat::BC3),
+		// BC4_UNORM, BC4_SNORM
+		80 => Some(DdsFormat::BC4),
+		81 => Some(DdsFormat::BC4Signed),
+		// BC5_UNORM, BC5_SNORM
+		83 => Some(DdsFormat::BC5),
+		84 => Some(DdsFormat::BC5Signed),
+		// B5G6R5_UNORM
+		85 => Some(DdsFormat::RGB565),
+		// B8G8R8A8_UNORM, B8G8R8A8_UNORM_SRGB
+		87 | 91 => Some(DdsFormat::BGRA8),
+		// B8G8R8X8_UNORM, B8G8R8X8_UNORM_SRGB
+		88 | 93 => Some(DdsFormat::BGRX8),
+		// BC6H_UF16, BC6H_SF16
+		95 => Some(DdsFormat::BC6H),
+		96 => Some(DdsFormat::BC6HSigned),
+		// BC7_UNORM, BC7_UNORM_SRGB
+		98 | 99 => Some(DdsFormat::BC7),
+		_ => None,
+	}
+}
+
+fn dxgi_from_format(format: DdsFormat) -> Option<u32> {
+	match format {
+		DdsFormat::RGBA8 => Some(28),
+		DdsFormat::BGRA8 => Some(87),
+		DdsFormat::BGRX8 => Some(88),
+		// there is no DXGI format with an unused alpha channel in the RGBA order
+		DdsFormat::RGBX8 => None,
+		DdsFormat::RGB565 => Some(85),
+		// there is no DXGI format with red in the highest bits
+		DdsFormat::RGBA4444 => None,
+		DdsFormat::L8 => Some(61),
+		DdsFormat::A8 => Some(65),
+		DdsFormat::BC1 => Some(71),
+		DdsFormat::BC3 => Some(77),
+		DdsFormat::BC4 => Some(80),
+		DdsFormat::BC4Signed => Some(81),
+		DdsFormat::BC5 => Some(83),
+		DdsFormat::BC5Signed => Some(84),
+		DdsFormat::BC6H => Some(95),
+		DdsFormat::BC6HSigned => Some(96),
+		DdsFormat::BC7 => Some(98),
+	}
+}
+
+/// Finds the uncompressed format described by the bit count and the RGBA masks of a legacy
+/// header.
+fn format_from_masks(flags: u32, bit_count: u32, masks: [u32; 4]) -> Option<DdsFormat> {
+	let has_alpha = flags & DDPF_ALPHAPIXELS != 0;
+	let alpha_mask = if has_alpha { masks[3] } else { 0 };
+
+	if flags & DDPF_RGB != 0 {
+		match (bit_count, masks[0], masks[1], masks[2], alpha_mask) {
+			(32, 0xFF, 0xFF00, 0xFF_0000, 0xFF00_0000) => Some(DdsFormat::RGBA8),
+			(32, 0xFF_0000, 0xFF00, 0xFF, 0xFF00_0000) => Some(DdsFormat::BGRA8),
+			(32, 0xFF, 0xFF00, 0xFF_0000, 0) => Some(DdsFormat::RGBX8),
+			(32, 0xFF_0000, 0xFF00, 0xFF, 0) => Some(DdsFormat::BGRX8),
+			(16, 0xF800, 0x07E0, 0x001F, 0) => Some(DdsFormat::RGB565),
+			(16, 0xF000, 0x0F00, 0x00F0, 0x000F) => Some(DdsFormat::RGBA4444),
+			_ => None,
+		}
+	} else if flags & DDPF_LUMINANCE != 0 && bit_count == 8 && !has_alpha {
+		Some(DdsFormat::L8)
+	} else if flags & DDPF_ALPHA != 0 && bit_count == 8 {
+		Some(DdsFormat::A8)
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Builds a `.dds` header with the given pixel format. The DX10 header is added when `dx10`
+	/// is set.
+	fn build_header(
+		width: u32,
+		height: u32,
+		mipmap_count: u32,
+		pixel_format: [u32; 8],
+		caps2: u32,
+		dx10: Option<[u32; 5]>,
+	) -> Vec<u8> {
+		let mut header = [0u32; 31];
+		header[0] = HEADER_SIZE;
+		header[2] = height;
+		header[3] = width;
+		header[6] = mipmap_count;
+		header[18..26].copy_from_slice(&pixel_format);
+		header[27] = caps2;
+
+		let mut file = MAGIC.to_vec();
+		file.extend(header.iter().flat_map(|value| value.to_le_bytes()));
+		file.extend(dx10.iter().flatten().flat_map(|value| value.to_le_bytes()));
+		file
+	}
+
+	fn four_cc_pixel_format(four_cc: &[u8; 4]) -> [u32; 8] {
+		[
+			PIXEL_FORMAT_SIZE,
+			DDPF_FOURCC,
+			u32::from_le_bytes(*four_cc),
+			0,
+			0,
+			0,
+			0,
+			0,
+		]
+	}
+
+	/// Creates a BC1 block where every pixel has the given color.
+	fn solid_bc1_block(color: u16) -> [u8; 8] {
+		let [lo, hi] = color.to_le_bytes();
+		[lo, hi, lo, hi, 0, 0, 0, 0]
+	}
+
+	#[test]
+	fn parse_dxt1_with_mipmaps() {
+		let mut file = build_header(8, 4, 3, four_cc_pixel_format(b"DXT1"), 0, None);
+		// 8x4 is 2 blocks, 4x2 and 2x1 are 1 block each
+		file.extend(solid_bc1_block(0xF800).repeat(2));
+		file.extend(solid_bc1_block(0x07E0));
+		file.extend(solid_bc1_block(0x001F));
+
+		let dds = DdsFile::parse(&file).unwrap();
+		assert_eq!(
+			dds.description,
+			DdsDescription {
+				width: 8,
+				height: 4,
+				depth: 1,
+				mipmap_count: 3,
+				array_size: 1,
+				is_cubemap: false,
+				format: DdsFormat::BC1,
+				four_cc: Some(*b"DXT1"),
+				dxgi_format: None,
+			}
+		);
+
+		let surfaces = dds.decode_surfaces().unwrap();
+		assert_eq!(surfaces.len(), 3);
+		let expected = [[248, 0, 0, 255], [0, 252, 0, 255], [0, 0, 248, 255]];
+		for (surface, expected) in surfaces.iter().zip(expected) {
+			let (width, height) = dds.description.mipmap_dimensions(surface.level);
+			assert_eq!((surface.width, surface.height), (width, height));
+			assert_eq!(surface.data, expected.repeat((width * height) as usize));
+		}
+	}
+
+	#[test]
+	fn parse_dx10_array() {
+		// R8G8B8A8_UNORM, 2D texture, array of 3
+		let dx10 = [28, 3, 0, 3, 0];
+		let mut file = build_header(1, 1, 1, four_cc_pixel_format(b"DX10"), 0, Some(dx10));
+		file.extend([0, 0, 0, 255, 1, 1, 1, 255, 2, 2, 2, 255]);
+
+		let dds = DdsFile::parse(&file).unwrap();
+		assert_eq!(dds.description.format, DdsFormat::RGBA8);
+		assert_eq!(dds.description.dxgi_format, Some(28));
+		assert_eq!(dds.description.array_size, 3);
+		assert_eq!(dds.description.layer_count(), 3);
+
+		let surfaces = dds.decode_surfaces().unwrap();
+		for (layer, surface) in surfaces.iter().enumerate() {
+			assert_eq!(surface.layer, layer as u32);
+			assert_eq!(surface.data, [layer as u8, layer as u8, layer as u8, 255]);
+		}
+	}
+
+	#[test]
+	fn parse_dx10_cubemap() {
+		// BC7_UNORM, 2D texture, cubemap
+		let dx10 = [98, 3, DDS_RESOURCE_MISC_TEXTURECUBE, 1, 0];
+		let mut file = build_header(4, 4, 1, four_cc_pixel_format(b"DX10"), 0, Some(dx10));
+		// mode 6 blocks, the first byte has to be set for a valid mode
+		let mut block = [0u8; 16];
+		block[0] = 0x40;
+		file.extend(block.repeat(6));
+
+		let dds = DdsFile::parse(&file).unwrap();
+		assert_eq!(dds.description.format, DdsFormat::BC7);
+		assert!(dds.description.is_cubemap);
+		assert_eq!(dds.description.layer_count(), 6);
+		assert_eq!(dds.decode_surfaces().unwrap().len(), 6);
+	}
+
+	#[test]
+	fn parse_legacy_cubemap() {
+		let mut file = build_header(
+			4,
+			4,
+			1,
+			four_cc_pixel_format(b"DXT1"),
+			DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES,
+			None,
+		);
+		file.extend(vec![0u8; 8 * 6]);
+
+		let dds = DdsFile::parse(&file).unwrap();
+		assert!(dds.description.is_cubemap);
+		assert_eq!(dds.description.layer_count(), 6);
+
+		// a cubemap with only some faces is not supported
+		let file = build_header(
+			4,
+			4,
+			1,
+			four_cc_pixel_format(b"DXT1"),
+			DDSCAPS2_CUBEMAP | 0x400,
+			None,
+		);
+		assert!(matches!(
+			DdsFile::parse(&file),
+			Err(DdsDecodeError::DdsFileError(DdsFileError::PartialCubemap))
+		));
+	}
+
+	#[test]
+	fn parse_uncompressed_masks() {
+		let pixel_format = [
+			PIXEL_FORMAT_SIZE,
+			DDPF_RGB | DDPF_ALPHAPIXELS,
+			0,
+			32,
+			0xFF_0000,
+			0xFF00,
+			0xFF,
+			0xFF00_0000,
+		];
+		let mut file = build_header(1, 1, 0, pixel_format, 0, None);
+		file.extend([1, 2, 3, 4]);
+
+		let dds = DdsFile::parse(&file).unwrap();
+		assert_eq!(dds.description.format, DdsFormat::BGRA8);
+		assert_eq!(dds.description.mipmap_count, 1);
+		assert_eq!(dds.decode_surface(0, 0).unwrap().data, [3, 2, 1, 4]);
+
+		// without an alpha mask, the alpha channel is unused
+		let pixel_format = [
+			PIXEL_FORMAT_SIZE,
+			DDPF_RGB,
+			0,
+			32,
+			0xFF_0000,
+			0xFF00,
+			0xFF,
+			0,
+		];
+		let mut file = build_header(1, 1, 0, pixel_format, 0, None);
+		file.extend([1, 2, 3, 4]);
+
+		let dds = DdsFile::parse(&file).unwrap();
+		assert_eq!(dds.description.format, DdsFormat::BGRX8);
+		assert_eq!(dds.decode_surface(0, 0).unwrap().data, [3, 2, 1, 255]);
+
+		let pixel_format = [PIXEL_FORMAT_SIZE, DDPF_LUMINANCE, 0, 8, 0xFF, 0, 0, 0];
+		let file = build_header(1, 1, 1, pixel_format, 0, None);
+		let dds = DdsFile::parse(&file).unwrap();
+		assert_eq!(dds.description.format, DdsFormat::L8);
+	}
+
+	#[test]
+	fn reject_invalid_files() {
+		assert!(matches!(
+			DdsFile::parse(b"not a dds file"),
+			Err(DdsDecodeError::DdsFileError(DdsFileError::InvalidMagic))
+		));
+
+		let file = build_header(4, 4, 1, four_cc_pixel_format(b"DXT3"), 0, None);
+		assert!(matches!(
+			DdsFile::parse(&file),
+			Err(DdsDecodeError::DdsFileError(DdsFileError::UnsupportedFourCc(
+				four_cc
+			))) if &four_cc == b"DXT3"
+		));
+
+		// the data of the surface is missing
+		let file = build_header(4, 4, 1, four_cc_pixel_format(b"DXT1"), 0, None);
+		let dds = DdsFile::parse(&file).unwrap();
+		assert!(matches!(
+			dds.decode_surface(0, 0),
+			Err(DdsDecodeError::DdsFileError(
+				DdsFileError::UnexpectedEndOfFile
+			))
+		));
+		assert!(matches!(
+			dds.decode_surface(0, 1),
+			Err(DdsDecodeError::DdsFileError(DdsFileError::InvalidSurface(
+				0, 1
+			)))
+		));
+	}
+
+	#[test]
+	fn reject_malformed_headers() {
+		for mipmap_count in [4, 33, u32::MAX] {
+			let file = build_header(4, 4, mipmap_count, four_cc_pixel_format(b"DXT1"), 0, None);
+			assert!(matches!(
+				DdsFile::parse(&file),
+				Err(DdsDecodeError::DdsFileError(DdsFileError::InvalidMipmapCount(
+					count, 4, 4, 3
+				))) if count == mipmap_count
+			));
+		}
+
+		// 32 levels is the most a texture can have
+		let file = build_header(1 << 31, 1, 32, four_cc_pixel_format(b"DXT1"), 0, None);
+		let dds = DdsFile::parse(&file).unwrap();
+		assert!(matches!(
+			dds.decode_surface(0, 31),
+			Err(DdsDecodeError::DdsFileError(
+				DdsFileError::UnexpectedEndOfFile
+			))
+		));
+
+		// R8G8B8A8_UNORM, 2D texture, more layers than there is data
+		for (array_size, misc_flag) in [
+			(u32::MAX, 0),
+			(3, 0),
+			(u32::MAX / 3, DDS_RESOURCE_MISC_TEXTURECUBE),
+		] {
+			let dx10 = [28, 3, misc_flag, array_size, 0];
+			let mut file = build_header(1, 1, 1, four_cc_pixel_format(b"DX10"), 0, Some(dx10));
+			file.extend([0, 0]);
+			assert!(matches!(
+				DdsFile::parse(&file),
+				Err(DdsDecodeError::DdsFileError(DdsFileError::InvalidArraySize(
+					size, 2
+				))) if size == array_size
+			));
+		}
+
+		// the size of the surface does not fit in memory
+		let file = build_header(
+			u32::MAX,
+			u32::MAX,
+			1,
+			four_cc_pixel_format(b"DX10"),
+			0,
+			Some([28, 3, 0, 1, 0]),
+		);
+		let dds = DdsFile::parse(&file).unwrap();
+		assert!(matches!(
+			dds.surface_data(0, 0),
+			Err(DdsDecodeError::DdsFileError(
+				DdsFileError::UnexpectedEndOfFile
+			))
+		));
+	}
+
+	#[test]
+	fn write_and_parse_round_trip() {
+		// 8x8 BC1 cubemap array with 2 cubemaps and 2 mipmap levels
+		let layer_size = 4 * 8 + 8;
+		let data = (0..12u8)
+			.flat_map(|layer| vec![layer; layer_size])
+			.collect::<Vec<_>>();
+		let description = DdsDescription::new(DdsFormat::BC1, 8, 8, 2, 2, true);
+
+		let mut file = vec![];
+		write_dds(&mut file, &description, &data).unwrap();
+		assert_eq!(
+			file.len(),
+			4 + HEADER_SIZE as usize + DX10_HEADER_SIZE + data.len()
+		);
+
+		let dds = DdsFile::parse(&file).unwrap();
+		assert_eq!(dds.description, description);
+		assert_eq!(dds.description.layer_count(), 12);
+		for layer in 0..12 {
+			assert_eq!(
+				dds.surface_data(layer, 0).unwrap(),
+				vec![layer as u8; 4 * 8]
+			);
+			assert_eq!(dds.surface_data(layer, 1).unwrap(), vec![layer as u8; 8]);
+		}
+	}
+
+	#[test]
+	fn write_sets_legacy_fields() {
+		let description = DdsDescription::new(DdsFormat::BC7, 8, 4, 1, 1, false);
+		let mut file = vec![];
+		write_dds(&mut file, &description, &[0; 2 * 16]).unwrap();
+
+		let read_u32 =
+			|offset: usize| u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap());
+		assert_eq!(read_u32(8), DDSD_REQUIRED | DDSD_LINEARSIZE);
+		assert_eq!(read_u32(20), 2 * 16);
+		assert_eq!(read_u32(108), DDSCAPS_TEXTURE);
+		assert_eq!(read_u32(112), 0);
+		// DXGI format and resource dimension
+		assert_eq!(read_u32(128), 98);
+		assert_eq!(read_u32(132), DDS_DIMENSION_TEXTURE2D);
+	}
+
+	#[test]
+	fn write_rejects_wrong_data_size() {
+		let description = DdsDescription::new(DdsFormat::BC1, 4, 4, 1, 1, false);
+		assert!(matches!(
+			write_dds(vec![], &description, &[0; 7]),
+			Err(DdsWriteError::InvalidDataSize(8, 7))
+		));
+
+		let description = DdsDescription::new(DdsFormat::RGBA4444, 1, 1, 1, 1, false);
+		assert!(matches!(
+			write_dds(vec![], &description, &[0; 2]),
+			Err(DdsWriteError::UnsupportedFormat(DdsFormat::RGBA4444))
+		));
+	}
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::DdsFormat;
+use crate::{DdsFormat, Rect};
 
 #[derive(Error, Debug)]
 pub enum DdsDecodeError {
@@ -8,6 +8,18 @@ pub enum DdsDecodeError {
 	Bc7Error(#[from] Bc7Error),
 	#[error("Unsupported format: {0:?}")]
 	UnsupportedFormat(DdsFormat),
+	#[error("DDS file error: {0}")]
+	DdsFileError(#[from] DdsFileError),
+	#[error("Invalid stride {0}, expected at least {1} bytes")]
+	InvalidStride(usize, usize),
+	#[error("Insufficient data, expected {expected} bytes but found {actual}")]
+	InsufficientData { expected: usize, actual: usize },
+	#[error("Region {0:?} does not fit in a {1}x{2} image")]
+	InvalidRegion(Rect, usize, usize),
+	#[error("Output buffer too small, expected at least {0} bytes but found {1}")]
+	BufferTooSmall(usize, usize),
+	#[error("Image of {0}x{1} pixels is too large")]
+	ImageTooLarge(usize, usize),
 }
 
 #[derive(Error, Debug)]
@@ -15,3 +27,49 @@ pub enum Bc7Error {
 	#[error("Invalid block mode: {0}")]
 	InvalidBc7BlockMode(u8),
 }
+
+#[derive(Error, Debug)]
+pub enum DdsFileError {
+	#[error("Invalid header magic (expected 'DDS ')")]
+	InvalidMagic,
+	#[error("Invalid header size: {0} (expected 124)")]
+	InvalidHeaderSize(u32),
+	#[error("Invalid pixel format size: {0} (expected 32)")]
+	InvalidPixelFormatSize(u32),
+	#[error("Unsupported FourCC: {0:?}")]
+	UnsupportedFourCc([u8; 4]),
+	#[error("Unsupported DXGI format: {0}")]
+	UnsupportedDxgiFormat(u32),
+	#[error("Unsupported uncompressed pixel format, flags {0:#x}")]
+	UnsupportedPixelFormat(u32),
+	#[error("Volume textures are not supported, found depth {0}")]
+	VolumeTexture(u32),
+	#[error("Cubemaps without all 6 faces are not supported")]
+	PartialCubemap,
+	#[error("Invalid mipmap count: {0} (a {1}x{2} texture has at most {3} levels)")]
+	InvalidMipmapCount(u32, u32, u32, u32),
+	#[error("Invalid array size: {0} (the file can hold at most {1} layers)")]
+	InvalidArraySize(u32, usize),
+	#[error("Invalid surface: layer {0}, mipmap level {1}")]
+	InvalidSurface(u32, u32),
+	#[error("Unexpected end of file")]
+	UnexpectedEndOfFile,
+}
+
+#[derive(Error, Debug)]
+pub enum DdsEncodeError {
+	#[error("Encoding to {0:?} is not supported")]
+	UnsupportedFormat(DdsFormat),
+	#[error("Invalid data size, expected {0} bytes but found {1}")]
+	InvalidDataSize(usize, usize),
+}
+
+#[derive(Error, Debug)]
+pub enum DdsWriteError {
+	#[error("IO error: {0}")]
+	IoError(#[from] std::io::Error),
+	#[error("Format {0:?} can not be stored in a DDS file")]
+	UnsupportedFormat(DdsFormat),
+	#[error("Invalid data size, expected {0} bytes but found {1}")]
+	InvalidDataSize(usize, usize),
+}
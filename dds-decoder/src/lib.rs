@@ -1,9 +1,14 @@
-use errors::DdsDecodeError;
+use errors::{DdsDecodeError, DdsEncodeError};
 
 mod bc1;
 mod bc3;
+mod bc4;
+mod bc5;
+mod bc6h;
 mod bc7;
+pub mod dds;
 pub mod errors;
+mod uncompressed;
 mod util;
 
 pub fn decode_image(
@@ -12,15 +17,280 @@ pub fn decode_image(
 	width: usize,
 	height: usize,
 ) -> Result<Vec<u8>, DdsDecodeError> {
+	let size = width
+		.checked_mul(height)
+		.and_then(|pixels| pixels.checked_mul(4))
+		.ok_or(DdsDecodeError::ImageTooLarge(width, height))?;
+	let mut final_pixels = vec![0u8; size];
+	decode_image_into(format, data, width, height, &mut final_pixels, width * 4)?;
+	Ok(final_pixels)
+}
+
+/// Decodes an image to RGBA8, writing it to `output` instead of allocating a new buffer.
+///
+/// Each row starts `stride` bytes after the previous one, which must be at least `width * 4`.
+/// Any bytes between the end of a row and the start of the next one are left untouched.
+pub fn decode_image_into(
+	format: DdsFormat,
+	data: &[u8],
+	width: usize,
+	height: usize,
+	output: &mut [u8],
+	stride: usize,
+) -> Result<(), DdsDecodeError> {
+	let row_size = width
+		.checked_mul(4)
+		.ok_or(DdsDecodeError::ImageTooLarge(width, height))?;
+	if stride < row_size {
+		return Err(DdsDecodeError::InvalidStride(stride, row_size));
+	}
+
+	let required_size = match height {
+		0 => 0,
+		_ => stride
+			.checked_mul(height - 1)
+			.and_then(|size| size.checked_add(row_size))
+			.ok_or(DdsDecodeError::ImageTooLarge(width, height))?,
+	};
+	if output.len() < required_size {
+		return Err(DdsDecodeError::BufferTooSmall(required_size, output.len()));
+	}
+
+	if width == 0 || height == 0 {
+		return Ok(());
+	}
+
+	check_data_size(format, data, width, height)?;
+
+	match format {
+		DdsFormat::RGBA8
+		| DdsFormat::RGBX8
+		| DdsFormat::BGRX8
+		| DdsFormat::RGB565
+		| DdsFormat::RGBA4444
+		| DdsFormat::L8
+		| DdsFormat::A8
+		| DdsFormat::BGRA8 => uncompressed::read_image(format, data, width, height, output, stride),
+		DdsFormat::BC1 => bc1::read_image(data, width, height, output, stride),
+		DdsFormat::BC3 => bc3::read_image(data, width, height, output, stride),
+		DdsFormat::BC4 => bc4::read_image(data, width, height, false, output, stride),
+		DdsFormat::BC4Signed => bc4::read_image(data, width, height, true, output, stride),
+		DdsFormat::BC5 => bc5::read_image(data, width, height, false, output, stride),
+		DdsFormat::BC5Signed => bc5::read_image(data, width, height, true, output, stride),
+		DdsFormat::BC6H => bc6h::read_image(data, width, height, false, output, stride),
+		DdsFormat::BC6HSigned => bc6h::read_image(data, width, height, true, output, stride),
+		DdsFormat::BC7 => bc7::read_image(data, width, height, output, stride)?,
+	}
+
+	Ok(())
+}
+
+/// Decodes a single 4x4 block of a block-compressed format.
+///
+/// Any data after the first block is ignored.
+pub fn decode_block(format: DdsFormat, block: &[u8]) -> Result<ColorBlock, DdsDecodeError> {
+	let block_size = format.block_size();
+	if block.len() < block_size {
+		return Err(DdsDecodeError::InsufficientData {
+			expected: block_size,
+			actual: block.len(),
+		});
+	}
+
+	let block = &block[..block_size];
+	match format {
+		DdsFormat::RGBA8
+		| DdsFormat::BGRA8
+		| DdsFormat::RGBX8
+		| DdsFormat::BGRX8
+		| DdsFormat::RGB565
+		| DdsFormat::RGBA4444
+		| DdsFormat::L8
+		| DdsFormat::A8 => Err(DdsDecodeError::UnsupportedFormat(format)),
+		DdsFormat::BC1 => Ok(bc1::decode_block(block)),
+		DdsFormat::BC3 => Ok(bc3::decode_block(block)),
+		DdsFormat::BC4 => Ok(bc4::decode_block(block, false)),
+		DdsFormat::BC4Signed => Ok(bc4::decode_block(block, true)),
+		DdsFormat::BC5 => Ok(bc5::decode_block(block, false)),
+		DdsFormat::BC5Signed => Ok(bc5::decode_block(block, true)),
+		DdsFormat::BC6H => Ok(bc6h::decode_block(block, false)),
+		DdsFormat::BC6HSigned => Ok(bc6h::decode_block(block, true)),
+		DdsFormat::BC7 => Ok(bc7::blocks::decode(block)?),
+	}
+}
+
+/// Decodes a rectangle of an image to RGBA8. Only the blocks that overlap the rectangle are
+/// decoded, so this is much faster than [decode_image] for small parts of large images.
+///
+/// The result is `rect.width * rect.height * 4` bytes.
+pub fn decode_region(
+	format: DdsFormat,
+	data: &[u8],
+	width: usize,
+	height: usize,
+	rect: Rect,
+) -> Result<Vec<u8>, DdsDecodeError> {
+	let fits = |start: usize, size: usize, limit: usize| {
+		start.checked_add(size).is_some_and(|end| end <= limit)
+	};
+	if !fits(rect.x, rect.width, width) || !fits(rect.y, rect.height, height) {
+		return Err(DdsDecodeError::InvalidRegion(rect, width, height));
+	}
+
+	let size = rect
+		.width
+		.checked_mul(rect.height)
+		.and_then(|pixels| pixels.checked_mul(4))
+		.ok_or(DdsDecodeError::ImageTooLarge(rect.width, rect.height))?;
+	let mut final_pixels = vec![0u8; size];
+	if final_pixels.is_empty() {
+		return Ok(final_pixels);
+	}
+
+	check_data_size(format, data, width, height)?;
+
+	let block_size = format.block_size();
+	if !format.is_block_compressed() {
+		let convert = uncompressed::converter(format);
+		for (row, line) in final_pixels.chunks_exact_mut(rect.width * 4).enumerate() {
+			let offset = ((rect.y + row) * width + rect.x) * block_size;
+			let pixels = data[offset..offset + rect.width * block_size].chunks_exact(block_size);
+			for (pixel, color) in line.chunks_exact_mut(4).zip(pixels) {
+				pixel.copy_from_slice(&convert(color));
+			}
+		}
+
+		return Ok(final_pixels);
+	}
+
+	let blocks_x = usize::max(1, width.div_ceil(4));
+	for block_y in rect.y / 4..(rect.y + rect.height).div_ceil(4) {
+		for block_x in rect.x / 4..(rect.x + rect.width).div_ceil(4) {
+			let offset = (block_y * blocks_x + block_x) * block_size;
+			let block = decode_block(format, &data[offset..])?;
+
+			// only copy the part of the block that falls inside the rectangle
+			let min_x = usize::max(rect.x, block_x * 4);
+			let max_x = usize::min(rect.x + rect.width, block_x * 4 + 4);
+			let min_y = usize::max(rect.y, block_y * 4);
+			let max_y = usize::min(rect.y + rect.height, block_y * 4 + 4);
+			for y in min_y..max_y {
+				for x in min_x..max_x {
+					let target_index = ((y - rect.y) * rect.width + (x - rect.x)) * 4;
+					final_pixels[target_index..target_index + 4]
+						.copy_from_slice(&block[y % 4][x % 4].components);
+				}
+			}
+		}
+	}
+
+	Ok(final_pixels)
+}
+
+/// Encodes an RGBA8 image to a block-compressed format. Only BC1, BC3 and BC7 are supported.
+///
+/// The encoder favors speed over quality: the endpoints of each block are fit to the range of its
+/// colors, without refining them afterwards.
+pub fn encode_image(
+	format: DdsFormat,
+	data: &[u8],
+	width: usize,
+	height: usize,
+) -> Result<Vec<u8>, DdsEncodeError> {
+	let expected_size = width * height * 4;
+	if data.len() != expected_size {
+		return Err(DdsEncodeError::InvalidDataSize(expected_size, data.len()));
+	}
+
+	let block_size = format.block_size();
+	let encoded = match format {
+		DdsFormat::BC1 => {
+			util::encode_blocks(data, width, height, block_size, bc1::encode::encode_block)
+		}
+		DdsFormat::BC3 => {
+			util::encode_blocks(data, width, height, block_size, bc3::encode::encode_block)
+		}
+		DdsFormat::BC7 => {
+			util::encode_blocks(data, width, height, block_size, bc7::encode::encode_block)
+		}
+		_ => return Err(DdsEncodeError::UnsupportedFormat(format)),
+	};
+
+	tracing::debug!("image encoded");
+
+	Ok(encoded)
+}
+
+/// Rebuilds the blue channel of an RGBA8 normal map from the red and green channels.
+///
+/// Normal maps are often stored with only the X and Y components (such as in BC5), since the Z
+/// component can be calculated from them as `sqrt(1 - x^2 - y^2)`.
+pub fn reconstruct_normal_z(data: &mut [u8]) {
+	for pixel in data.chunks_exact_mut(4) {
+		let x = pixel[0] as f32 / 255.0 * 2.0 - 1.0;
+		let y = pixel[1] as f32 / 255.0 * 2.0 - 1.0;
+		let z = f32::max(0.0, 1.0 - x * x - y * y).sqrt();
+		pixel[2] = ((z * 0.5 + 0.5) * 255.0).round() as u8;
+	}
+}
+
+/// Decodes an HDR image to RGB half floats, stored as their raw bits. Each pixel takes 3 values.
+///
+/// Unlike [decode_image], this does not tone map the colors.
+pub fn decode_image_f16(
+	format: DdsFormat,
+	data: &[u8],
+	width: usize,
+	height: usize,
+) -> Result<Vec<u16>, DdsDecodeError> {
+	check_data_size(format, data, width, height)?;
+
 	match format {
-		DdsFormat::BC1 => Ok(bc1::read_image(data, width, height)),
-		DdsFormat::BC3 => Ok(bc3::read_image(data, width, height)),
-		DdsFormat::BC7 => Ok(bc7::read_image(data, width, height)?),
+		DdsFormat::BC6H => Ok(bc6h::read_image_f16(data, width, height, false)),
+		DdsFormat::BC6HSigned => Ok(bc6h::read_image_f16(data, width, height, true)),
 		_ => Err(DdsDecodeError::UnsupportedFormat(format)),
 	}
 }
 
-type ColorBlock = [[Color4; 4]; 4];
+/// Decodes an HDR image to RGB 32-bit floats. Each pixel takes 3 values.
+///
+/// Unlike [decode_image], this does not tone map the colors.
+pub fn decode_image_f32(
+	format: DdsFormat,
+	data: &[u8],
+	width: usize,
+	height: usize,
+) -> Result<Vec<f32>, DdsDecodeError> {
+	check_data_size(format, data, width, height)?;
+
+	match format {
+		DdsFormat::BC6H => Ok(bc6h::read_image_f32(data, width, height, false)),
+		DdsFormat::BC6HSigned => Ok(bc6h::read_image_f32(data, width, height, true)),
+		_ => Err(DdsDecodeError::UnsupportedFormat(format)),
+	}
+}
+
+/// Makes sure `data` holds the entire image, so a truncated image is not silently decoded with
+/// missing pixels. Any data after the image is ignored.
+fn check_data_size(
+	format: DdsFormat,
+	data: &[u8],
+	width: usize,
+	height: usize,
+) -> Result<(), DdsDecodeError> {
+	let expected = format.data_size(width, height);
+	if data.len() < expected {
+		return Err(DdsDecodeError::InsufficientData {
+			expected,
+			actual: data.len(),
+		});
+	}
+
+	Ok(())
+}
+
+/// A 4x4 block of pixels, indexed as `block[y][x]`.
+pub type ColorBlock = [[Color4; 4]; 4];
 
 #[derive(Default, Debug, Copy, Clone)]
 pub struct Color4 {
@@ -44,6 +314,15 @@ impl Color4 {
 	}
 }
 
+/// A rectangle in an image, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+	pub x: usize,
+	pub y: usize,
+	pub width: usize,
+	pub height: usize,
+}
+
 pub struct DecodedImage {
 	width: usize,
 	pub data: Vec<Color4>,
@@ -60,10 +339,34 @@ impl DecodedImage {
 	}
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DdsFormat {
 	/// Uncompressed RGBA8
 	RGBA8,
+	/// Uncompressed BGRA8, RGBA8 with the red and blue channels swapped.
+	BGRA8,
+	/// Uncompressed RGBX8, RGBA8 where the alpha channel is unused.
+	///
+	/// Decodes to an opaque image.
+	RGBX8,
+	/// Uncompressed BGRX8, BGRA8 where the alpha channel is unused.
+	///
+	/// Decodes to an opaque image.
+	BGRX8,
+	/// Uncompressed 16-bit color, with 5 bits of red in the highest bits, 6 bits of green and 5
+	/// bits of blue.
+	RGB565,
+	/// Uncompressed 16-bit color with alpha, with 4 bits for each channel. Red is stored in the
+	/// highest bits, alpha in the lowest.
+	RGBA4444,
+	/// Uncompressed 8-bit luminance.
+	///
+	/// Decodes to a grayscale image.
+	L8,
+	/// Uncompressed 8-bit alpha.
+	///
+	/// Decodes to black with the given alpha.
+	A8,
 	/// BC1/DXT1: Three-channel color with alpha channel.
 	///
 	/// Uses DDS magic "DXT1"
@@ -72,8 +375,329 @@ pub enum DdsFormat {
 	///
 	/// Uses DDS magic "DXT5"
 	BC3,
-	/// BC6H: Three-channel high dynamic range (HDR) color.
+	/// BC4: One-channel color, stored as unsigned values.
+	///
+	/// Decodes to a grayscale image.
+	BC4,
+	/// BC4: One-channel color, stored as signed values.
+	BC4Signed,
+	/// BC5: Two-channel color, stored as unsigned values. Mostly used for normal maps.
+	///
+	/// Decodes to the red and green channels, see [reconstruct_normal_z] to fill in the blue
+	/// channel.
+	BC5,
+	/// BC5: Two-channel color, stored as signed values.
+	BC5Signed,
+	/// BC6H: Three-channel high dynamic range (HDR) color, stored as unsigned half floats.
+	///
+	/// Decoding to RGBA8 tone maps the colors, use [decode_image_f16] or [decode_image_f32] to get
+	/// the original values.
 	BC6H,
+	/// BC6H: Three-channel high dynamic range (HDR) color, stored as signed half floats.
+	BC6HSigned,
 	/// BC7: Three-channel color, alpha channel optional.
 	BC7,
 }
+
+impl DdsFormat {
+	/// Returns the size in bytes of an image of the given dimensions in this format.
+	///
+	/// Block-compressed formats are padded to a multiple of 4 pixels in each dimension.
+	pub fn data_size(&self, width: usize, height: usize) -> usize {
+		if self.is_block_compressed() {
+			let blocks_x = usize::max(1, width.div_ceil(4));
+			let blocks_y = usize::max(1, height.div_ceil(4));
+			blocks_x * blocks_y * self.block_size()
+		} else {
+			width * height * self.block_size()
+		}
+	}
+
+	/// Like [Self::data_size], but returns `None` instead of overflowing.
+	pub(crate) fn checked_data_size(&self, width: usize, height: usize) -> Option<usize> {
+		if self.is_block_compressed() {
+			let blocks_x = usize::max(1, width.div_ceil(4));
+			let blocks_y = usize::max(1, height.div_ceil(4));
+			blocks_x
+				.checked_mul(blocks_y)?
+				.checked_mul(self.block_size())
+		} else {
+			width.checked_mul(height)?.checked_mul(self.block_size())
+		}
+	}
+
+	/// Returns whether this format is stored as 4x4 blocks, rather than as individual pixels.
+	pub fn is_block_compressed(&self) -> bool {
+		!matches!(
+			self,
+			DdsFormat::RGBA8
+				| DdsFormat::BGRA8
+				| DdsFormat::RGBX8
+				| DdsFormat::BGRX8
+				| DdsFormat::RGB565
+				| DdsFormat::RGBA4444
+				| DdsFormat::L8
+				| DdsFormat::A8
+		)
+	}
+
+	/// Returns the size in bytes of a single 4x4 block, or of a single pixel for uncompressed
+	/// formats.
+	pub fn block_size(&self) -> usize {
+		match self {
+			DdsFormat::RGBA8 | DdsFormat::BGRA8 | DdsFormat::RGBX8 | DdsFormat::BGRX8 => 4,
+			DdsFormat::RGB565 | DdsFormat::RGBA4444 => 2,
+			DdsFormat::L8 | DdsFormat::A8 => 1,
+			DdsFormat::BC1 | DdsFormat::BC4 | DdsFormat::BC4Signed => 8,
+			DdsFormat::BC3
+			| DdsFormat::BC5
+			| DdsFormat::BC5Signed
+			| DdsFormat::BC6H
+			| DdsFormat::BC6HSigned
+			| DdsFormat::BC7 => 16,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reconstruct_normal_z_points_outwards() {
+		// a flat normal (0, 0) becomes (0, 0, 1), and a normal along an axis gets a Z of 0
+		let mut data = [128, 128, 0, 255, 255, 128, 0, 255];
+		reconstruct_normal_z(&mut data);
+		assert_eq!(data, [128, 128, 255, 255, 255, 128, 128, 255]);
+	}
+
+	#[test]
+	fn decode_image_into_uses_stride() {
+		let data = [1, 2, 3, 4, 5, 6, 7, 8];
+		let mut output = [0xFF; 2 * 12 - 4];
+		decode_image_into(DdsFormat::BGRA8, &data, 1, 2, &mut output, 12).unwrap();
+		assert_eq!(
+			output,
+			[
+				3, 2, 1, 4, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, //
+				7, 6, 5, 8, 0xFF, 0xFF, 0xFF, 0xFF,
+			]
+		);
+
+		assert!(matches!(
+			decode_image_into(DdsFormat::BGRA8, &data, 1, 2, &mut output, 2),
+			Err(DdsDecodeError::InvalidStride(2, 4))
+		));
+		assert!(matches!(
+			decode_image_into(DdsFormat::BGRA8, &data, 1, 2, &mut output[..15], 12),
+			Err(DdsDecodeError::BufferTooSmall(16, 15))
+		));
+	}
+
+	#[test]
+	fn decode_image_rejects_overflowing_dimensions() {
+		let mut output = [0; 16];
+		assert!(matches!(
+			decode_image_into(DdsFormat::BGRA8, &[], usize::MAX / 2, 1, &mut output, 16),
+			Err(DdsDecodeError::ImageTooLarge(_, 1))
+		));
+		assert!(matches!(
+			decode_image_into(DdsFormat::BGRA8, &[], 1, usize::MAX, &mut output, 4),
+			Err(DdsDecodeError::ImageTooLarge(1, _))
+		));
+		assert!(matches!(
+			decode_image(DdsFormat::BGRA8, &[], usize::MAX, 2),
+			Err(DdsDecodeError::ImageTooLarge(_, 2))
+		));
+	}
+
+	#[test]
+	fn decode_image_rejects_truncated_data() {
+		// a 5x5 BC1 image takes 2x2 blocks of 8 bytes
+		let data = [0; 2 * 2 * 8];
+		assert!(decode_image(DdsFormat::BC1, &data, 5, 5).is_ok());
+		assert!(matches!(
+			decode_image(DdsFormat::BC1, &data[..31], 5, 5),
+			Err(DdsDecodeError::InsufficientData {
+				expected: 32,
+				actual: 31
+			})
+		));
+		assert!(matches!(
+			decode_image_f16(DdsFormat::BC6H, &data[..16], 5, 5),
+			Err(DdsDecodeError::InsufficientData {
+				expected: 64,
+				actual: 16
+			})
+		));
+	}
+
+	/// Crops an RGBA8 image to the given rectangle.
+	fn crop(image: &[u8], width: usize, rect: Rect) -> Vec<u8> {
+		(rect.y..rect.y + rect.height)
+			.flat_map(|y| {
+				let offset = (y * width + rect.x) * 4;
+				image[offset..offset + rect.width * 4].to_vec()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn decode_region_matches_full_image() {
+		// 6x6 BC1 image of 2x2 blocks, each with their own solid color
+		let data = [0xF800u16, 0x07E0, 0x001F, 0xFFFF]
+			.iter()
+			.flat_map(|color| {
+				let [lo, hi] = color.to_le_bytes();
+				[lo, hi, lo, hi, 0, 0, 0, 0]
+			})
+			.collect::<Vec<_>>();
+		let image = decode_image(DdsFormat::BC1, &data, 6, 6).unwrap();
+
+		let rect = Rect {
+			x: 3,
+			y: 2,
+			width: 3,
+			height: 3,
+		};
+		let region = decode_region(DdsFormat::BC1, &data, 6, 6, rect).unwrap();
+		assert_eq!(region, crop(&image, 6, rect));
+
+		let data = (0..6 * 6 * 4).map(|i| i as u8).collect::<Vec<_>>();
+		let image = decode_image(DdsFormat::BGRA8, &data, 6, 6).unwrap();
+		let region = decode_region(DdsFormat::BGRA8, &data, 6, 6, rect).unwrap();
+		assert_eq!(region, crop(&image, 6, rect));
+
+		let rect = Rect { x: 4, ..rect };
+		assert!(matches!(
+			decode_region(DdsFormat::BGRA8, &data, 6, 6, rect),
+			Err(DdsDecodeError::InvalidRegion(_, 6, 6))
+		));
+
+		let rect = Rect {
+			x: 1,
+			y: usize::MAX,
+			width: 1,
+			height: 2,
+		};
+		assert!(matches!(
+			decode_region(DdsFormat::BGRA8, &data, 6, 6, rect),
+			Err(DdsDecodeError::InvalidRegion(_, 6, 6))
+		));
+	}
+
+	#[test]
+	fn decode_block_rejects_uncompressed_formats() {
+		let block = [0xE0, 0x07, 0xE0, 0x07, 0, 0, 0, 0];
+		let colors = decode_block(DdsFormat::BC1, &block).unwrap();
+		assert!(colors
+			.iter()
+			.flatten()
+			.all(|c| c.components == [0, 252, 0, 255]));
+
+		assert!(matches!(
+			decode_block(DdsFormat::RGBA8, &block),
+			Err(DdsDecodeError::UnsupportedFormat(DdsFormat::RGBA8))
+		));
+		assert!(matches!(
+			decode_block(DdsFormat::BC7, &block),
+			Err(DdsDecodeError::InsufficientData {
+				expected: 16,
+				actual: 8
+			})
+		));
+	}
+
+	/// Builds a 37x23 RGBA8 test image with smooth gradients, some sharp edges and noise. The odd
+	/// size makes sure the edge blocks are padded.
+	fn test_image(with_alpha: bool) -> (Vec<u8>, usize, usize) {
+		let (width, height) = (37, 23);
+		let mut seed = 0x1234_5678u32;
+		let mut noise = move || {
+			seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+			((seed >> 16) % 16) as usize
+		};
+
+		let mut image = Vec::with_capacity(width * height * 4);
+		for y in 0..height {
+			for x in 0..width {
+				let edge = if (x / 9 + y / 7) % 2 == 0 { 0 } else { 60 };
+				image.push((x * 255 / width + noise()) as u8);
+				image.push((y * 255 / height + edge) as u8);
+				image.push(((x + y) * 255 / (width + height)) as u8);
+				image.push(if with_alpha {
+					(x * 7 + y * 3) as u8
+				} else {
+					255
+				});
+			}
+		}
+
+		(image, width, height)
+	}
+
+	/// Calculates the peak signal-to-noise ratio between 2 images, in decibels.
+	fn psnr(a: &[u8], b: &[u8]) -> f64 {
+		assert_eq!(a.len(), b.len());
+		let squared_error = a
+			.iter()
+			.zip(b)
+			.map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+			.sum::<f64>();
+		let mean_squared_error = squared_error / a.len() as f64;
+		10.0 * (255.0 * 255.0 / mean_squared_error).log10()
+	}
+
+	/// Encodes and decodes the test image, returning the PSNR of the result.
+	fn round_trip_psnr(format: DdsFormat, with_alpha: bool) -> f64 {
+		let (image, width, height) = test_image(with_alpha);
+		let encoded = encode_image(format, &image, width, height).unwrap();
+		assert_eq!(encoded.len(), format.data_size(width, height));
+
+		let decoded = decode_image(format, &encoded, width, height).unwrap();
+		psnr(&image, &decoded)
+	}
+
+	#[test]
+	fn encode_rejects_unsupported_formats() {
+		assert!(matches!(
+			encode_image(DdsFormat::BC6H, &[0; 16 * 4], 4, 4),
+			Err(DdsEncodeError::UnsupportedFormat(DdsFormat::BC6H))
+		));
+		assert!(matches!(
+			encode_image(DdsFormat::BC1, &[0; 15 * 4], 4, 4),
+			Err(DdsEncodeError::InvalidDataSize(64, 60))
+		));
+	}
+
+	#[test]
+	fn encode_bc1_round_trip() {
+		let psnr = round_trip_psnr(DdsFormat::BC1, false);
+		assert!(psnr > 29.0, "PSNR is {psnr}");
+
+		// BC1 only has 1-bit alpha, pixels with an alpha below 128 become transparent
+		let (image, width, height) = test_image(true);
+		let encoded = encode_image(DdsFormat::BC1, &image, width, height).unwrap();
+		let decoded = decode_image(DdsFormat::BC1, &encoded, width, height).unwrap();
+		for (original, decoded) in image.chunks_exact(4).zip(decoded.chunks_exact(4)) {
+			let expected_alpha = if original[3] < 128 { 0 } else { 255 };
+			assert_eq!(decoded[3], expected_alpha);
+		}
+	}
+
+	#[test]
+	fn encode_bc3_round_trip() {
+		let psnr = round_trip_psnr(DdsFormat::BC3, true);
+		assert!(psnr > 29.0, "PSNR is {psnr}");
+	}
+
+	#[test]
+	fn encode_bc7_round_trip() {
+		// opaque blocks can use mode 1, which handles the sharp edges better
+		let psnr = round_trip_psnr(DdsFormat::BC7, false);
+		assert!(psnr > 36.0, "PSNR is {psnr}");
+
+		let psnr = round_trip_psnr(DdsFormat::BC7, true);
+		assert!(psnr > 26.0, "PSNR is {psnr}");
+	}
+}
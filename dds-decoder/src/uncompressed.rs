@@ -0,0 +1,141 @@
+use crate::DdsFormat;
+
+/// Converts an uncompressed image to RGBA8. 16-bit formats are stored as little-endian.
+pub fn read_image(
+	format: DdsFormat,
+	data: &[u8],
+	width: usize,
+	height: usize,
+	output: &mut [u8],
+	stride: usize,
+) {
+	let pixel_size = format.block_size();
+	let convert = converter(format);
+
+	for (row, line) in output
+		.chunks_mut(stride)
+		.zip(data.chunks(width * pixel_size))
+		.take(height)
+	{
+		for (pixel, color) in row.chunks_exact_mut(4).zip(line.chunks_exact(pixel_size)) {
+			pixel.copy_from_slice(&convert(color));
+		}
+	}
+
+	tracing::debug!("image decoded");
+}
+
+/// Returns the function that converts a single pixel of an uncompressed format to RGBA8.
+pub fn converter(format: DdsFormat) -> fn(&[u8]) -> [u8; 4] {
+	match format {
+		DdsFormat::RGBA8 => |pixel| [pixel[0], pixel[1], pixel[2], pixel[3]],
+		DdsFormat::BGRA8 => |pixel| [pixel[2], pixel[1], pixel[0], pixel[3]],
+		DdsFormat::RGBX8 => |pixel| [pixel[0], pixel[1], pixel[2], 255],
+		DdsFormat::BGRX8 => |pixel| [pixel[2], pixel[1], pixel[0], 255],
+		DdsFormat::RGB565 => |pixel| {
+			let color = u16::from_le_bytes([pixel[0], pixel[1]]);
+			[
+				expand_bits(color >> 11, 5),
+				expand_bits(color >> 5, 6),
+				expand_bits(color, 5),
+				255,
+			]
+		},
+		DdsFormat::RGBA4444 => |pixel| {
+			let color = u16::from_le_bytes([pixel[0], pixel[1]]);
+			[
+				expand_bits(color >> 12, 4),
+				expand_bits(color >> 8, 4),
+				expand_bits(color >> 4, 4),
+				expand_bits(color, 4),
+			]
+		},
+		DdsFormat::L8 => |pixel| [pixel[0], pixel[0], pixel[0], 255],
+		DdsFormat::A8 => |pixel| [0, 0, 0, pixel[0]],
+		_ => unreachable!("{format:?} is not an uncompressed format"),
+	}
+}
+
+/// Scales the lowest `bits` bits of a value to 8 bits, by repeating the highest bits in the lower
+/// bits. This makes sure that the maximum value maps to 255.
+fn expand_bits(value: u16, bits: u32) -> u8 {
+	let value = value & ((1 << bits) - 1);
+	((value << (8 - bits)) | (value >> (2 * bits - 8))) as u8
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn read_image(format: DdsFormat, data: &[u8], width: usize, height: usize) -> Vec<u8> {
+		let mut image = vec![0; width * height * 4];
+		super::read_image(format, data, width, height, &mut image, width * 4);
+		image
+	}
+
+	#[test]
+	fn expand_bits_covers_full_range() {
+		assert_eq!(expand_bits(0, 5), 0);
+		assert_eq!(expand_bits(0b11111, 5), 255);
+		assert_eq!(expand_bits(0b111111, 6), 255);
+		assert_eq!(expand_bits(0b1111, 4), 255);
+		assert_eq!(expand_bits(0b1000, 4), 0x88);
+		// higher bits are ignored
+		assert_eq!(expand_bits(0xFFF0, 4), 0);
+	}
+
+	#[test]
+	fn read_bgra8() {
+		let data = [1, 2, 3, 4, 5, 6, 7, 8];
+		let image = read_image(DdsFormat::BGRA8, &data, 2, 1);
+		assert_eq!(image, [3, 2, 1, 4, 7, 6, 5, 8]);
+	}
+
+	#[test]
+	fn read_bgrx8() {
+		let data = [1, 2, 3, 4, 5, 6, 7, 8];
+		let image = read_image(DdsFormat::BGRX8, &data, 2, 1);
+		assert_eq!(image, [3, 2, 1, 255, 7, 6, 5, 255]);
+	}
+
+	#[test]
+	fn read_rgb565() {
+		let colors: [u16; 4] = [0xF800, 0x07E0, 0x001F, 0x8410];
+		let data = colors
+			.iter()
+			.flat_map(|color| color.to_le_bytes())
+			.collect::<Vec<_>>();
+		let image = read_image(DdsFormat::RGB565, &data, 2, 2);
+		assert_eq!(
+			image,
+			[
+				255, 0, 0, 255, //
+				0, 255, 0, 255, //
+				0, 0, 255, 255, //
+				132, 130, 132, 255,
+			]
+		);
+	}
+
+	#[test]
+	fn read_rgba4444() {
+		let data = 0x1F80u16.to_le_bytes();
+		let image = read_image(DdsFormat::RGBA4444, &data, 1, 1);
+		assert_eq!(image, [0x11, 0xFF, 0x88, 0x00]);
+	}
+
+	#[test]
+	fn read_l8() {
+		let image = read_image(DdsFormat::L8, &[0, 128, 255], 3, 1);
+		assert_eq!(
+			image,
+			[0, 0, 0, 255, 128, 128, 128, 255, 255, 255, 255, 255]
+		);
+	}
+
+	#[test]
+	fn read_a8() {
+		let image = read_image(DdsFormat::A8, &[0, 128], 1, 2);
+		assert_eq!(image, [0, 0, 0, 0, 0, 0, 0, 128]);
+	}
+}
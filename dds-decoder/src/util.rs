@@ -1,4 +1,206 @@
-use crate::Color4;
+use crate::{Color4, ColorBlock};
+
+/// Decodes an image made up of 4x4 blocks of `block_size` bytes into RGBA8 pixels, writing them
+/// to `output` where each row starts `stride` bytes after the previous one.
+///
+/// Each block is written straight into the output buffer, cropping any pixels that fall outside
+/// of the image. With the `rayon` feature enabled, rows of blocks are decoded in parallel.
+pub fn decode_blocks<E, F>(
+	data: &[u8],
+	width: usize,
+	height: usize,
+	block_size: usize,
+	output: &mut [u8],
+	stride: usize,
+	decode_block: F,
+) -> Result<(), E>
+where
+	E: Send,
+	F: Fn(usize, &[u8]) -> Result<ColorBlock, E> + Sync,
+{
+	if width == 0 || height == 0 {
+		return Ok(());
+	}
+
+	let blocks_x = usize::max(1, width.div_ceil(4));
+	let block_row_bytes = blocks_x * block_size;
+	let output = &mut output[..stride * (height - 1) + width * 4];
+
+	let decode_row = |(block_y, (pixels, blocks)): (usize, (&mut [u8], &[u8]))| {
+		let rows = usize::min(4, height - block_y * 4);
+		for (block_x, block) in blocks.chunks_exact(block_size).enumerate() {
+			let block = decode_block(block_y * blocks_x + block_x, block)?;
+
+			let x = block_x * 4;
+			let columns = usize::min(4, width - x);
+			for (y, block_row) in block.iter().enumerate().take(rows) {
+				let offset = y * stride + x * 4;
+				pixels[offset..offset + columns * 4]
+					.chunks_exact_mut(4)
+					.zip(block_row)
+					.for_each(|(pixel, color)| pixel.copy_from_slice(&color.components));
+			}
+		}
+
+		Ok(())
+	};
+
+	#[cfg(feature = "rayon")]
+	{
+		use rayon::prelude::*;
+
+		output
+			.par_chunks_mut(stride * 4)
+			.zip(data.par_chunks(block_row_bytes))
+			.enumerate()
+			.try_for_each(decode_row)
+	}
+
+	#[cfg(not(feature = "rayon"))]
+	{
+		output
+			.chunks_mut(stride * 4)
+			.zip(data.chunks(block_row_bytes))
+			.enumerate()
+			.try_for_each(decode_row)
+	}
+}
+
+/// Encodes an RGBA8 image to 4x4 blocks of `block_size` bytes.
+///
+/// Pixels past the edge of the image are filled in by repeating the last row and column, so they
+/// don't pull the endpoints of the edge blocks away from the real colors. With the `rayon` feature
+/// enabled, rows of blocks are encoded in parallel.
+pub fn encode_blocks<F>(
+	data: &[u8],
+	width: usize,
+	height: usize,
+	block_size: usize,
+	encode_block: F,
+) -> Vec<u8>
+where
+	F: Fn(&[[u8; 4]; 16], &mut [u8]) + Sync,
+{
+	let blocks_x = usize::max(1, width.div_ceil(4));
+	let blocks_y = usize::max(1, height.div_ceil(4));
+	let mut encoded = vec![0u8; blocks_x * blocks_y * block_size];
+	if width == 0 || height == 0 {
+		return encoded;
+	}
+
+	let encode_row = |(block_y, blocks): (usize, &mut [u8])| {
+		for (block_x, block) in blocks.chunks_exact_mut(block_size).enumerate() {
+			let mut pixels = [[0u8; 4]; 16];
+			for (i, pixel) in pixels.iter_mut().enumerate() {
+				let x = usize::min(block_x * 4 + i % 4, width - 1);
+				let y = usize::min(block_y * 4 + i / 4, height - 1);
+				let offset = (y * width + x) * 4;
+				pixel.copy_from_slice(&data[offset..offset + 4]);
+			}
+
+			encode_block(&pixels, block);
+		}
+	};
+
+	#[cfg(feature = "rayon")]
+	{
+		use rayon::prelude::*;
+
+		encoded
+			.par_chunks_mut(blocks_x * block_size)
+			.enumerate()
+			.for_each(encode_row);
+	}
+
+	#[cfg(not(feature = "rayon"))]
+	{
+		encoded
+			.chunks_mut(blocks_x * block_size)
+			.enumerate()
+			.for_each(encode_row);
+	}
+
+	encoded
+}
+
+/// Finds 2 endpoints for a set of pixels, using the first `N` channels.
+///
+/// The endpoints lie on the principal axis of the pixels, which is the direction in which they vary
+/// the most, and span the full range of the pixels projected on that axis.
+pub fn range_fit<const N: usize>(pixels: &[[u8; 4]]) -> ([f32; N], [f32; N]) {
+	debug_assert!(!pixels.is_empty());
+
+	let count = pixels.len() as f32;
+	let mut mean = [0f32; N];
+	for pixel in pixels {
+		for c in 0..N {
+			mean[c] += pixel[c] as f32 / count;
+		}
+	}
+
+	let mut covariance = [[0f32; N]; N];
+	for pixel in pixels {
+		for i in 0..N {
+			for j in 0..N {
+				covariance[i][j] += (pixel[i] as f32 - mean[i]) * (pixel[j] as f32 - mean[j]);
+			}
+		}
+	}
+
+	// power iteration, this converges to the eigenvector with the largest eigenvalue
+	let mut axis = [1f32; N];
+	for _ in 0..8 {
+		let mut next = [0f32; N];
+		for i in 0..N {
+			next[i] = (0..N).map(|j| covariance[i][j] * axis[j]).sum();
+		}
+
+		let length = next.iter().fold(0f32, |max, value| max.max(value.abs()));
+		if length < f32::EPSILON {
+			// all pixels are the same color
+			return (mean, mean);
+		}
+		axis = next.map(|value| value / length);
+	}
+
+	let project =
+		|pixel: &[u8; 4]| -> f32 { (0..N).map(|c| (pixel[c] as f32 - mean[c]) * axis[c]).sum() };
+	let (min, max) = pixels
+		.iter()
+		.map(project)
+		.fold((f32::MAX, f32::MIN), |(min, max), t| {
+			(min.min(t), max.max(t))
+		});
+
+	let length_squared: f32 = axis.iter().map(|value| value * value).sum();
+	let endpoint = |t: f32| {
+		let mut endpoint = [0f32; N];
+		for c in 0..N {
+			endpoint[c] = (mean[c] + axis[c] * t / length_squared).clamp(0.0, 255.0);
+		}
+		endpoint
+	};
+
+	(endpoint(min), endpoint(max))
+}
+
+/// Finds the color in the palette closest to the pixel, using the first `channels` channels.
+/// Returns its index and the squared error.
+pub fn closest_color(palette: &[Color4], pixel: &[u8; 4], channels: usize) -> (usize, u32) {
+	palette
+		.iter()
+		.map(|color| {
+			(0..channels)
+				.map(|c| {
+					let diff = color.components[c] as i32 - pixel[c] as i32;
+					(diff * diff) as u32
+				})
+				.sum::<u32>()
+		})
+		.enumerate()
+		.min_by_key(|&(_, error)| error)
+		.expect("palette is not empty")
+}
 
 /// Unpacks a u16 color value into an RGBA color. Note that the alpha component is always 255 and it
 /// is read as BGR, not RGB.
@@ -53,3 +255,37 @@ pub fn interp_color_2_transparent(color_0: Color4, color_1: Color4) -> Color4 {
 pub fn interp_color_3_transparent(_: Color4, _: Color4) -> Color4 {
 	Color4::TRANSPARENT
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decode_blocks_crops_padding() {
+		// a 5x5 image takes 2x2 blocks, each block is filled with its index. each row is followed by
+		// a padding byte that should be left alone.
+		let data = [0, 1, 2, 3];
+		let mut pixels = vec![0xFF; 21 * 4 + 20];
+		decode_blocks::<(), _>(&data, 5, 5, 1, &mut pixels, 21, |index, block| {
+			assert_eq!(index, block[0] as usize);
+			let color = Color4 {
+				components: [block[0]; 4],
+			};
+			Ok([[color; 4]; 4])
+		})
+		.unwrap();
+
+		let indices = pixels
+			.chunks(21)
+			.flat_map(|row| row.iter().step_by(4).copied())
+			.collect::<Vec<_>>();
+		#[rustfmt::skip]
+		assert_eq!(indices, [
+			0, 0, 0, 0, 1, 0xFF,
+			0, 0, 0, 0, 1, 0xFF,
+			0, 0, 0, 0, 1, 0xFF,
+			0, 0, 0, 0, 1, 0xFF,
+			2, 2, 2, 2, 3,
+		]);
+	}
+}
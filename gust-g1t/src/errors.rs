@@ -29,7 +29,30 @@ pub enum G1tReadError {
 	InvalidExtendedDataSize(u32),
 	#[error("Texture has no mipmaps")]
 	NoMipmaps,
+	#[error("Invalid mipmap level {0}, texture has {1} mipmaps")]
+	InvalidMipmapLevel(u8, u8),
+	#[error("Invalid layer {0}, texture has {1} layers")]
+	InvalidLayer(u32, u32),
+	#[error("Texture data ends at {0:#x}, past the end of the file at {1:#x}")]
+	TruncatedTextureData(u64, u64),
+	#[error("Texture of {0}x{1} pixels is too large")]
+	TextureTooLarge(u32, u32),
 
 	#[error("Unimplemented feature: {0}")]
 	Unimplemented(Cow<'static, str>),
 }
+
+#[derive(Error, Debug)]
+pub enum G1tWriteError {
+	#[error("IO error: {0}")]
+	IoError(#[from] std::io::Error),
+	#[error("Failed to read texture: {0}")]
+	ReadError(#[from] G1tReadError),
+	#[error("DDS write error: {0}")]
+	DdsWriteError(#[from] dds_decoder::errors::DdsWriteError),
+
+	#[error("Expected data for {0} textures, found {1}")]
+	TextureCountMismatch(usize, usize),
+	#[error("File is too large: {0} bytes (max 4 GiB)")]
+	FileTooLarge(u64),
+}
@@ -0,0 +1,441 @@
+//! Writing of `.ktx2` files, which can be uploaded to the GPU directly.
+//!
+//! See: <https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html>
+
+use std::{borrow::Cow, io::Write};
+
+use dds_decoder::DdsFormat;
+
+const IDENTIFIER: [u8; 12] = [
+	0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+/// The size of the identifier, header and index.
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+const KTX_WRITER: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// The contents of a `.ktx2` file.
+pub struct Ktx2Texture {
+	pub format: DdsFormat,
+	pub width: u32,
+	pub height: u32,
+	/// The amount of array layers, or 0 if the texture is not an array.
+	pub layer_count: u32,
+	/// 6 for cubemaps, 1 otherwise.
+	pub face_count: u32,
+	/// The data of every mipmap level, starting with the full-size image. Each level contains the
+	/// images for every layer, and for every face within that layer.
+	pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2Texture {
+	pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+		// Vulkan has no formats with an unused alpha channel, so those are stored with an opaque
+		// alpha channel instead
+		let (format, levels) = match self.format {
+			DdsFormat::RGBX8 => (DdsFormat::RGBA8, Cow::Owned(opaque_levels(&self.levels))),
+			DdsFormat::BGRX8 => (DdsFormat::BGRA8, Cow::Owned(opaque_levels(&self.levels))),
+			format => (format, Cow::Borrowed(&self.levels)),
+		};
+
+		let dfd = data_format_descriptor(format);
+		let kvd = key_value_data();
+
+		let level_count = levels.len();
+		let dfd_offset = HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE;
+		let kvd_offset = dfd_offset + dfd.len();
+		let data_offset = kvd_offset + kvd.len();
+
+		// the levels are stored from smallest to largest, each aligned to the block size
+		let alignment = level_alignment(format);
+		let mut level_offsets = vec![0; level_count];
+		let mut offset = data_offset;
+		for (level, data) in levels.iter().enumerate().rev() {
+			offset = offset.next_multiple_of(alignment);
+			level_offsets[level] = offset;
+			offset += data.len();
+		}
+
+		let mut file = Vec::with_capacity(offset);
+		file.extend_from_slice(&IDENTIFIER);
+		for value in [
+			vk_format(format),
+			type_size(format),
+			self.width,
+			self.height,
+			// pixel depth, 0 for 2D textures
+			0,
+			self.layer_count,
+			self.face_count,
+			level_count as u32,
+			// no supercompression
+			0,
+			dfd_offset as u32,
+			dfd.len() as u32,
+			kvd_offset as u32,
+			kvd.len() as u32,
+		] {
+			file.extend_from_slice(&value.to_le_bytes());
+		}
+		// no supercompression global data
+		file.extend_from_slice(&0u64.to_le_bytes());
+		file.extend_from_slice(&0u64.to_le_bytes());
+
+		for (data, offset) in levels.iter().zip(&level_offsets) {
+			let length = data.len() as u64;
+			file.extend_from_slice(&(*offset as u64).to_le_bytes());
+			file.extend_from_slice(&length.to_le_bytes());
+			// uncompressed length, the same since there is no supercompression
+			file.extend_from_slice(&length.to_le_bytes());
+		}
+
+		file.extend_from_slice(&dfd);
+		file.extend_from_slice(&kvd);
+
+		for (level, data) in levels.iter().enumerate().rev() {
+			file.resize(level_offsets[level], 0);
+			file.extend_from_slice(data);
+		}
+
+		writer.write_all(&file)
+	}
+}
+
+/// Copies the levels of a 32-bit format with an unused alpha channel, setting the alpha channel to
+/// opaque.
+fn opaque_levels(levels: &[Vec<u8>]) -> Vec<Vec<u8>> {
+	levels
+		.iter()
+		.map(|level| {
+			let mut level = level.clone();
+			level.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 255);
+			level
+		})
+		.collect()
+}
+
+/// Returns the `VkFormat` matching a format.
+pub fn vk_format(format: DdsFormat) -> u32 {
+	match format {
+		// formats with an unused alpha channel are written with an opaque alpha channel
+		DdsFormat::RGBA8 | DdsFormat::RGBX8 => 37, // VK_FORMAT_R8G8B8A8_UNORM
+		DdsFormat::BGRA8 | DdsFormat::BGRX8 => 44, // VK_FORMAT_B8G8R8A8_UNORM
+		DdsFormat::RGB565 => 4,                    // VK_FORMAT_R5G6B5_UNORM_PACK16
+		DdsFormat::RGBA4444 => 2,                  // VK_FORMAT_R4G4B4A4_UNORM_PACK16
+		DdsFormat::L8 => 9,                        // VK_FORMAT_R8_UNORM
+		DdsFormat::A8 => 1000470001,               // VK_FORMAT_A8_UNORM_KHR
+		DdsFormat::BC1 => 133,                     // VK_FORMAT_BC1_RGBA_UNORM_BLOCK
+		DdsFormat::BC3 => 137,                     // VK_FORMAT_BC3_UNORM_BLOCK
+		DdsFormat::BC4 => 139,                     // VK_FORMAT_BC4_UNORM_BLOCK
+		DdsFormat::BC4Signed => 140,               // VK_FORMAT_BC4_SNORM_BLOCK
+		DdsFormat::BC5 => 141,                     // VK_FORMAT_BC5_UNORM_BLOCK
+		DdsFormat::BC5Signed => 142,               // VK_FORMAT_BC5_SNORM_BLOCK
+		DdsFormat::BC6H => 143,                    // VK_FORMAT_BC6H_UFLOAT_BLOCK
+		DdsFormat::BC6HSigned => 144,              // VK_FORMAT_BC6H_SFLOAT_BLOCK
+		DdsFormat::BC7 => 145,                     // VK_FORMAT_BC7_UNORM_BLOCK
+	}
+}
+
+/// The size of the data type used for endianness conversion: 2 for packed 16-bit formats, 1
+/// otherwise.
+fn type_size(format: DdsFormat) -> u32 {
+	match format {
+		DdsFormat::RGB565 | DdsFormat::RGBA4444 => 2,
+		_ => 1,
+	}
+}
+
+/// Levels are aligned to the least common multiple of the block size and 4.
+fn level_alignment(format: DdsFormat) -> usize {
+	usize::max(4, format.block_size())
+}
+
+// channel types and qualifiers used in the data format descriptor
+const CHANNEL_RED: u8 = 0;
+const CHANNEL_GREEN: u8 = 1;
+const CHANNEL_BLUE: u8 = 2;
+const CHANNEL_ALPHA: u8 = 15;
+const QUALIFIER_SIGNED: u8 = 0x40;
+const QUALIFIER_FLOAT: u8 = 0x80;
+
+// color models
+const MODEL_RGBSDA: u8 = 1;
+const MODEL_BC1A: u8 = 128;
+const MODEL_BC3: u8 = 130;
+const MODEL_BC4: u8 = 131;
+const MODEL_BC5: u8 = 132;
+const MODEL_BC6H: u8 = 133;
+const MODEL_BC7: u8 = 134;
+
+/// A sample of the data format descriptor: `(bit offset, bit length, channel, lower, upper)`.
+type Sample = (u16, u8, u8, u32, u32);
+
+/// Builds the data format descriptor, which describes the layout of a texel block.
+fn data_format_descriptor(format: DdsFormat) -> Vec<u8> {
+	const UNORM: (u32, u32) = (0, u32::MAX);
+	const SNORM: (u32, u32) = (0x8000_0000, 0x7FFF_FFFF);
+	// 0.0 and 1.0 as floats
+	const UFLOAT: (u32, u32) = (0, 0x3F80_0000);
+	// -1.0 and 1.0 as floats
+	const SFLOAT: (u32, u32) = (0xBF80_0000, 0x3F80_0000);
+
+	let block = |offset, length, channel, (lower, upper): (u32, u32)| -> Sample {
+		(offset, length, channel, lower, upper)
+	};
+	let pixel =
+		|offset, length: u8, channel| -> Sample { (offset, length, channel, 0, (1 << length) - 1) };
+
+	let (model, samples): (u8, Vec<Sample>) = match format {
+		DdsFormat::RGBA8 | DdsFormat::RGBX8 => (
+			MODEL_RGBSDA,
+			vec![
+				pixel(0, 8, CHANNEL_RED),
+				pixel(8, 8, CHANNEL_GREEN),
+				pixel(16, 8, CHANNEL_BLUE),
+				pixel(24, 8, CHANNEL_ALPHA),
+			],
+		),
+		DdsFormat::BGRA8 | DdsFormat::BGRX8 => (
+			MODEL_RGBSDA,
+			vec![
+				pixel(0, 8, CHANNEL_BLUE),
+				pixel(8, 8, CHANNEL_GREEN),
+				pixel(16, 8, CHANNEL_RED),
+				pixel(24, 8, CHANNEL_ALPHA),
+			],
+		),
+		DdsFormat::RGB565 => (
+			MODEL_RGBSDA,
+			vec![
+				pixel(0, 5, CHANNEL_BLUE),
+				pixel(5, 6, CHANNEL_GREEN),
+				pixel(11, 5, CHANNEL_RED),
+			],
+		),
+		DdsFormat::RGBA4444 => (
+			MODEL_RGBSDA,
+			vec![
+				pixel(0, 4, CHANNEL_ALPHA),
+				pixel(4, 4, CHANNEL_BLUE),
+				pixel(8, 4, CHANNEL_GREEN),
+				pixel(12, 4, CHANNEL_RED),
+			],
+		),
+		DdsFormat::L8 => (MODEL_RGBSDA, vec![pixel(0, 8, CHANNEL_RED)]),
+		DdsFormat::A8 => (MODEL_RGBSDA, vec![pixel(0, 8, CHANNEL_ALPHA)]),
+		// channel 1 of BC1A means the block can contain transparent pixels
+		DdsFormat::BC1 => (MODEL_BC1A, vec![block(0, 64, 1, UNORM)]),
+		DdsFormat::BC3 => (
+			MODEL_BC3,
+			vec![
+				block(0, 64, CHANNEL_ALPHA, UNORM),
+				block(64, 64, CHANNEL_RED, UNORM),
+			],
+		),
+		DdsFormat::BC4 => (MODEL_BC4, vec![block(0, 64, CHANNEL_RED, UNORM)]),
+		DdsFormat::BC4Signed => (
+			MODEL_BC4,
+			vec![block(0, 64, CHANNEL_RED | QUALIFIER_SIGNED, SNORM)],
+		),
+		DdsFormat::BC5 => (
+			MODEL_BC5,
+			vec![
+				block(0, 64, CHANNEL_RED, UNORM),
+				block(64, 64, CHANNEL_GREEN, UNORM),
+			],
+		),
+		DdsFormat::BC5Signed => (
+			MODEL_BC5,
+			vec![
+				block(0, 64, CHANNEL_RED | QUALIFIER_SIGNED, SNORM),
+				block(64, 64, CHANNEL_GREEN | QUALIFIER_SIGNED, SNORM),
+			],
+		),
+		DdsFormat::BC6H => (
+			MODEL_BC6H,
+			vec![block(0, 128, CHANNEL_RED | QUALIFIER_FLOAT, UFLOAT)],
+		),
+		DdsFormat::BC6HSigned => (
+			MODEL_BC6H,
+			vec![block(
+				0,
+				128,
+				CHANNEL_RED | QUALIFIER_FLOAT | QUALIFIER_SIGNED,
+				SFLOAT,
+			)],
+		),
+		DdsFormat::BC7 => (MODEL_BC7, vec![block(0, 128, CHANNEL_RED, UNORM)]),
+	};
+
+	let block_dimension = if format.is_block_compressed() { 3 } else { 0 };
+	let block_size = 24 + samples.len() * 16;
+
+	let mut dfd = vec![];
+	// total size, including this field
+	dfd.extend_from_slice(&(4 + block_size as u32).to_le_bytes());
+	// vendor id and descriptor type, both 0 for the basic descriptor
+	dfd.extend_from_slice(&0u32.to_le_bytes());
+	// version 2, followed by the block size
+	dfd.extend_from_slice(&2u16.to_le_bytes());
+	dfd.extend_from_slice(&(block_size as u16).to_le_bytes());
+	// color model, BT.709 primaries, linear transfer function and straight alpha
+	dfd.extend_from_slice(&[model, 1, 1, 0]);
+	// texel block dimensions, minus 1
+	dfd.extend_from_slice(&[block_dimension, block_dimension, 0, 0]);
+	// bytes per plane, only the first plane is used
+	dfd.extend_from_slice(&[format.block_size() as u8, 0, 0, 0, 0, 0, 0, 0]);
+
+	for (offset, length, channel, lower, upper) in samples {
+		dfd.extend_from_slice(&offset.to_le_bytes());
+		dfd.extend_from_slice(&[length - 1, channel]);
+		// sample position, always the origin
+		dfd.extend_from_slice(&[0, 0, 0, 0]);
+		dfd.extend_from_slice(&lower.to_le_bytes());
+		dfd.extend_from_slice(&upper.to_le_bytes());
+	}
+
+	dfd
+}
+
+/// Builds the key/value data, which only contains the name of the writer.
+fn key_value_data() -> Vec<u8> {
+	let key_and_value = format!("KTXwriter\0{KTX_WRITER}\0");
+
+	let mut kvd = vec![];
+	kvd.extend_from_slice(&(key_and_value.len() as u32).to_le_bytes());
+	kvd.extend_from_slice(key_and_value.as_bytes());
+	kvd.resize(kvd.len().next_multiple_of(4), 0);
+	kvd
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn write_bc7_array() {
+		// 8x8 with 2 levels and 3 layers
+		let texture = Ktx2Texture {
+			format: DdsFormat::BC7,
+			width: 8,
+			height: 8,
+			layer_count: 3,
+			face_count: 1,
+			levels: vec![vec![1; 3 * 4 * 16], vec![2; 3 * 16]],
+		};
+		let mut file = vec![];
+		texture.write(&mut file).unwrap();
+
+		let reader = ::ktx2::Reader::new(&file).unwrap();
+		let header = reader.header();
+		assert_eq!(header.format, Some(::ktx2::Format::BC7_UNORM_BLOCK));
+		assert_eq!((header.pixel_width, header.pixel_height), (8, 8));
+		assert_eq!(header.pixel_depth, 0);
+		assert_eq!(header.layer_count, 3);
+		assert_eq!(header.face_count, 1);
+		assert_eq!(header.level_count, 2);
+		assert_eq!(header.supercompression_scheme, None);
+
+		let levels = reader.levels().collect::<Vec<_>>();
+		assert_eq!(levels[0].data, texture.levels[0]);
+		assert_eq!(levels[1].data, texture.levels[1]);
+
+		// the smallest level is stored first, and levels are aligned to the block size
+		let data_start =
+			|level: &::ktx2::Level| level.data.as_ptr() as usize - file.as_ptr() as usize;
+		assert!(data_start(&levels[1]) < data_start(&levels[0]));
+		assert_eq!(data_start(&levels[0]) % 16, 0);
+		assert_eq!(data_start(&levels[1]) % 16, 0);
+
+		let (key, value) = reader.key_value_data().next().unwrap();
+		assert_eq!(key, "KTXwriter");
+		assert_eq!(value, format!("{KTX_WRITER}\0").as_bytes());
+	}
+
+	#[test]
+	fn write_data_format_descriptor() {
+		let texture = Ktx2Texture {
+			format: DdsFormat::BC5Signed,
+			width: 4,
+			height: 4,
+			layer_count: 0,
+			face_count: 6,
+			levels: vec![vec![0; 6 * 16]],
+		};
+		let mut file = vec![];
+		texture.write(&mut file).unwrap();
+
+		let reader = ::ktx2::Reader::new(&file).unwrap();
+		assert_eq!(
+			reader.header().format,
+			Some(::ktx2::Format::BC5_SNORM_BLOCK)
+		);
+		assert_eq!(reader.header().face_count, 6);
+
+		let blocks = reader.dfd_blocks().collect::<Vec<_>>();
+		assert_eq!(blocks.len(), 1);
+		let block = ::ktx2::DfdBlockBasic::parse(blocks[0].data).unwrap();
+		assert_eq!(block.header.color_model, Some(::ktx2::ColorModel::BC5));
+		assert_eq!(block.header.bytes_planes[0], 16);
+		assert_eq!(
+			block
+				.header
+				.texel_block_dimensions
+				.map(|dimension| dimension.get()),
+			[4, 4, 1, 1]
+		);
+
+		let samples = block.sample_information().collect::<Vec<_>>();
+		assert_eq!(samples.len(), 2);
+		assert_eq!(samples[1].bit_offset, 64);
+		assert_eq!(samples[1].bit_length.get(), 64);
+		assert_eq!(samples[1].channel_type, CHANNEL_GREEN);
+		assert!(samples[1]
+			.channel_type_qualifiers
+			.contains(::ktx2::ChannelTypeQualifiers::SIGNED));
+	}
+
+	#[test]
+	fn write_packed_format() {
+		let texture = Ktx2Texture {
+			format: DdsFormat::RGB565,
+			width: 2,
+			height: 1,
+			layer_count: 0,
+			face_count: 1,
+			levels: vec![vec![1, 2, 3, 4]],
+		};
+		let mut file = vec![];
+		texture.write(&mut file).unwrap();
+
+		let reader = ::ktx2::Reader::new(&file).unwrap();
+		assert_eq!(
+			reader.header().format,
+			Some(::ktx2::Format::R5G6B5_UNORM_PACK16)
+		);
+		assert_eq!(reader.header().type_size, 2);
+		assert_eq!(reader.levels().next().unwrap().data, [1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn write_opaque_unused_alpha() {
+		let texture = Ktx2Texture {
+			format: DdsFormat::BGRX8,
+			width: 2,
+			height: 1,
+			layer_count: 0,
+			face_count: 1,
+			levels: vec![vec![1, 2, 3, 4, 5, 6, 7, 8]],
+		};
+		let mut file = vec![];
+		texture.write(&mut file).unwrap();
+
+		let reader = ::ktx2::Reader::new(&file).unwrap();
+		assert_eq!(reader.header().format, Some(::ktx2::Format::B8G8R8A8_UNORM));
+		assert_eq!(
+			reader.levels().next().unwrap().data,
+			[1, 2, 3, 255, 5, 6, 7, 255]
+		);
+	}
+}
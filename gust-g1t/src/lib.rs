@@ -1,33 +1,58 @@
 pub mod errors;
+mod ktx2;
+mod swizzle;
 mod util;
 
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 
-use errors::G1tReadError;
-use scroll::IOread;
+use errors::{G1tReadError, G1tWriteError};
+use scroll::{IOread, IOwrite};
 use tracing::{debug, trace, warn};
 
 pub struct GustG1t {
 	pub header: G1tHeader,
 	pub textures: Vec<TextureInfo>,
+	/// The extra section after the offset table, its size is stored in [G1tHeader::extra_size].
+	///
+	/// Its meaning is unknown, so it is kept as-is.
+	pub extra_data: Vec<u32>,
+	/// Unknown data between the global texture flags and the offset table.
+	header_extra_data: Vec<u8>,
+	/// Unknown data between the extra section and the first texture.
+	extra_padding: Vec<u8>,
 }
 
 /// A single texture in a g1t file.
 pub struct TextureInfo {
 	pub header: G1tTextureHeader,
-	#[allow(unused)]
-	global_flag: GlobalTextureFlags,
+	pub global_flags: GlobalTextureFlags,
 	pub height: u32,
 	pub width: u32,
 	pub frames: u32,
+	/// Whether every frame is a cubemap with 6 faces.
+	///
+	/// The flag that marks cubemaps in .g1t files is not known yet, so this is detected from the
+	/// size of the texture data instead: a cubemap holds exactly 6 layers per frame. It can be
+	/// changed for textures where that guess is wrong.
+	pub cubemap: bool,
+	/// The values of the extended data section, without its size.
+	extended_data: Vec<u32>,
+	/// The offset of the texture header from the offset table, as stored in the file.
+	offset: u32,
 	absolute_data_offset: u64,
+	/// The size of the data of all layers and mipmap levels, up to the next texture.
+	data_size: u64,
 }
 
 impl GustG1t {
 	pub fn read(mut reader: impl Read + Seek) -> Result<Self, G1tReadError> {
 		let (header, global_flags) = G1tHeader::read(&mut reader)?;
+		let endian = header.endian;
 		debug_assert_eq!(header.texture_count, global_flags.len() as u32);
 
+		let file_size = util::stream_len(&mut reader)?;
+		let header_extra_data = util::read_until(&mut reader, header.header_size as u64)?;
+
 		trace!(
 			"position after reading header: {}",
 			reader.stream_position()?
@@ -41,33 +66,57 @@ impl GustG1t {
 		reader.seek(std::io::SeekFrom::Start(header.header_size as u64))?;
 		let mut offsets = Vec::with_capacity(header.texture_count as usize);
 		for _ in 0..header.texture_count {
-			let offset: u32 = reader.ioread()?;
+			let offset: u32 = reader.ioread_with(endian)?;
 			offsets.push(offset);
 		}
 		trace!(?offsets);
 
+		// each texture ends where the next one starts, or at the end of the file
+		let texture_starts = offsets
+			.iter()
+			.map(|&offset| header.header_size as u64 + offset as u64)
+			.collect::<Vec<_>>();
+		let extra_data = (0..header.extra_size / 4)
+			.map(|_| reader.ioread_with::<u32>(endian))
+			.collect::<Result<Vec<_>, _>>()?;
+		trace!(?extra_data);
+
+		let first_texture_start = texture_starts.iter().copied().min().unwrap_or(file_size);
+		let extra_padding = util::read_until(&mut reader, first_texture_start)?;
+
 		// read textures
 		let mut textures = vec![];
 		for (texture_index, offset) in offsets.into_iter().enumerate() {
 			let span = tracing::debug_span!("texture", texture_index, offset);
 			let _guard = span.enter();
 
-			let global_flag = global_flags[texture_index];
+			let global_flags = global_flags[texture_index];
+			if global_flags.unknown_bits() != 0 {
+				debug!(
+					"Unknown global texture flags: {:#x}",
+					global_flags.unknown_bits()
+				);
+			}
 
-			reader.seek(std::io::SeekFrom::Start(
-				(header.header_size + offset) as u64,
-			))?;
+			reader.seek(std::io::SeekFrom::Start(texture_starts[texture_index]))?;
 			let texture_header = G1tTextureHeader::read(&mut reader)?;
 			trace!(?texture_header);
+			if texture_header.flags.unknown_bits() != 0 {
+				debug!(
+					"Unknown texture flags: {:#x}",
+					texture_header.flags.unknown_bits()
+				);
+			}
 
 			// header has been read, image data comes now
 			let mut width = texture_header.width();
 			let mut height = texture_header.height();
 			let mut frames = 1;
+			let mut extended_data: Vec<u32> = vec![];
 
 			// possible extended data section
 			if texture_header.flags.contains(TextureFlags::EXTENDED_DATA) {
-				let extended_data_len: u32 = reader.ioread()?;
+				let extended_data_len: u32 = reader.ioread_with(endian)?;
 				trace!(?extended_data_len);
 
 				const HEADER_SIZE_FRAMES_DEPTH: u32 = 0x0C;
@@ -83,9 +132,13 @@ impl GustG1t {
 					return Err(G1tReadError::InvalidExtendedDataSize(extended_data_len));
 				}
 
+				for _ in 1..extended_data_len / 4 {
+					extended_data.push(reader.ioread_with(endian)?);
+				}
+
 				if extended_data_len >= HEADER_SIZE_FRAMES_DEPTH {
-					let depth: u32 = reader.ioread()?;
-					let texture_flags_2: u32 = reader.ioread()?;
+					let depth = extended_data[0];
+					let texture_flags_2 = extended_data[1];
 					let frames_from_flags =
 						((texture_flags_2 >> 28) & 0x0F) + ((texture_flags_2 >> 12) & 0xF0);
 					frames = if frames_from_flags == 0 {
@@ -98,45 +151,340 @@ impl GustG1t {
 				}
 
 				if extended_data_len >= HEADER_SIZE_NON_STANDARD_WIDTH {
-					width = reader.ioread()?;
+					width = extended_data[2];
 					trace!(?width, "non-standard width");
 				}
 
 				if extended_data_len >= HEADER_SIZE_NON_STANDARD_HEIGHT {
-					height = reader.ioread()?;
+					height = extended_data[3];
 					trace!(?height, "non-standard height");
 				}
 			}
 
+			let absolute_data_offset = reader.stream_position()?;
+			let data_end = texture_starts
+				.iter()
+				.copied()
+				.filter(|&start| start > texture_starts[texture_index])
+				.min()
+				.unwrap_or(file_size);
+
 			// need to create some kind of structure data here?
-			let texture_info = TextureInfo {
+			let mut texture_info = TextureInfo {
 				header: texture_header,
-				global_flag,
+				global_flags,
 				height,
 				width,
 				frames,
-				absolute_data_offset: reader.stream_position()?,
+				cubemap: false,
+				extended_data,
+				offset,
+				absolute_data_offset,
+				data_size: data_end.saturating_sub(absolute_data_offset),
 			};
+			texture_info.cubemap = texture_info.has_cubemap_size(header.platform);
+			if texture_info.cubemap {
+				debug!("Texture data holds 6 faces per frame, reading it as a cubemap");
+			}
 			textures.push(texture_info);
 		}
 
-		Ok(Self { header, textures })
+		Ok(Self {
+			header,
+			textures,
+			extra_data,
+			header_extra_data,
+			extra_padding,
+		})
 	}
 
-	pub fn read_image(
+	/// Writes a g1t file containing the textures of this file, in the same layout as [GustG1t::read]
+	/// expects.
+	///
+	/// The textures are stored in the same order as in the original file, which is not always the
+	/// order of their indices. Any gap after a texture is part of its data, so reading a file and
+	/// writing it back with the unchanged data results in the same bytes. The only exception is a
+	/// file where multiple textures share the same offset, those are written separately.
+	///
+	/// `texture_data` holds the data of each texture, in the same order as [GustG1t::textures] and
+	/// in the format returned by [GustG1t::read_texture_data]. The headers are written as they were
+	/// read, so new data needs to have the same format and dimensions as the original.
+	pub fn write(
+		&self,
+		texture_data: &[impl AsRef<[u8]>],
+		mut writer: impl Write,
+	) -> Result<(), G1tWriteError> {
+		if texture_data.len() != self.textures.len() {
+			return Err(G1tWriteError::TextureCountMismatch(
+				self.textures.len(),
+				texture_data.len(),
+			));
+		}
+
+		let endian = self.header.endian;
+		let texture_count = self.textures.len() as u32;
+		let header_size = G1tHeader::SIZE + texture_count * 4 + self.header_extra_data.len() as u32;
+
+		// the textures are stored back to back, right after the offset table and the extra data, in
+		// the order of their original offsets
+		let mut storage_order = (0..self.textures.len()).collect::<Vec<_>>();
+		storage_order.sort_by_key(|&index| self.textures[index].offset);
+
+		let mut offsets = vec![0; self.textures.len()];
+		let extra_size = self.extra_data.len() as u32 * 4;
+		let mut offset = (texture_count * 4 + extra_size) as u64 + self.extra_padding.len() as u64;
+		for &index in &storage_order {
+			offsets[index] = offset;
+			offset += self.textures[index].header_size() as u64
+				+ texture_data[index].as_ref().len() as u64;
+		}
+		let total_size = header_size as u64 + offset;
+		if total_size > u32::MAX as u64 {
+			return Err(G1tWriteError::FileTooLarge(total_size));
+		}
+
+		let header = G1tHeader {
+			header_size,
+			texture_count,
+			extra_size,
+			..self.header
+		};
+		header.write(&mut writer, total_size as u32)?;
+		for texture in &self.textures {
+			writer.iowrite_with(texture.global_flags.bits(), endian)?;
+		}
+		writer.write_all(&self.header_extra_data)?;
+
+		for offset in offsets {
+			writer.iowrite_with(offset as u32, endian)?;
+		}
+		for &value in &self.extra_data {
+			writer.iowrite_with(value, endian)?;
+		}
+		writer.write_all(&self.extra_padding)?;
+
+		for index in storage_order {
+			let texture = &self.textures[index];
+			texture.header.write(&mut writer)?;
+			if texture.header.flags.contains(TextureFlags::EXTENDED_DATA) {
+				let extended_data_len = (texture.extended_data.len() as u32 + 1) * 4;
+				writer.iowrite_with(extended_data_len, endian)?;
+				for &value in &texture.extended_data {
+					writer.iowrite_with(value, endian)?;
+				}
+			}
+			writer.write_all(texture_data[index].as_ref())?;
+		}
+
+		debug!(total_size, "g1t written");
+
+		Ok(())
+	}
+
+	/// Reads the raw data of a texture as it is stored in the file: every layer with all of its
+	/// mipmap levels, without unswizzling it. This is the format [GustG1t::write] expects.
+	pub fn read_texture_data(
 		&self,
 		texture: &TextureInfo,
 		mut reader: impl Read + Seek,
+	) -> Result<Vec<u8>, G1tReadError> {
+		reader.seek(std::io::SeekFrom::Start(texture.absolute_data_offset))?;
+
+		let mut data = vec![0u8; texture.data_size as usize];
+		reader.read_exact(&mut data)?;
+
+		Ok(data)
+	}
+
+	/// Reads the full-size image of a texture, as RGBA8.
+	///
+	/// For textures with multiple frames or cubemap faces, this is the first one.
+	pub fn read_image(
+		&self,
+		texture: &TextureInfo,
+		reader: impl Read + Seek,
 	) -> Result<Vec<u8>, G1tReadError> {
 		if texture.header.mipmaps > 1 {
-			// we can safely ignore mipmaps because the fill size image always comes first
-			// TODO: allow reading mipmaps
+			// the full size image always comes first, so the other levels don't need to be read
 			tracing::debug!(
 				"Detected {} mipmaps, they are ignored",
 				texture.header.mipmaps
 			);
 		}
 
+		if texture.layer_count() > 1 {
+			tracing::debug!(
+				"Detected {} layers, only the first one is read",
+				texture.layer_count()
+			);
+		}
+
+		self.read_mipmap(texture, 0, reader)
+	}
+
+	/// Reads every mipmap level of a texture, starting with the full-size image.
+	///
+	/// For textures with multiple frames or cubemap faces, these are the levels of the first one.
+	pub fn read_mipmaps(
+		&self,
+		texture: &TextureInfo,
+		mut reader: impl Read + Seek,
+	) -> Result<Vec<TextureImage>, G1tReadError> {
+		(0..texture.header.mipmaps)
+			.map(|level| {
+				let (width, height) = texture.mipmap_dimensions(level);
+				let data = self.read_mipmap(texture, level, &mut reader)?;
+				Ok(TextureImage {
+					width,
+					height,
+					data,
+				})
+			})
+			.collect()
+	}
+
+	/// Reads the full-size image of every frame or cubemap face of a texture.
+	///
+	/// See [TextureInfo::layer_count] for the order of the layers.
+	pub fn read_layers(
+		&self,
+		texture: &TextureInfo,
+		mut reader: impl Read + Seek,
+	) -> Result<Vec<TextureImage>, G1tReadError> {
+		(0..texture.layer_count())
+			.map(|layer| {
+				let data = self.read_layer(texture, layer, 0, &mut reader)?;
+				Ok(TextureImage {
+					width: texture.width,
+					height: texture.height,
+					data,
+				})
+			})
+			.collect()
+	}
+
+	/// Reads a single mipmap level of a texture, as RGBA8. Level 0 is the full-size image.
+	pub fn read_mipmap(
+		&self,
+		texture: &TextureInfo,
+		level: u8,
+		reader: impl Read + Seek,
+	) -> Result<Vec<u8>, G1tReadError> {
+		self.read_layer(texture, 0, level, reader)
+	}
+
+	/// Reads a single mipmap level of a single layer of a texture, as RGBA8.
+	///
+	/// See [TextureInfo::layer_count] for the order of the layers.
+	pub fn read_layer(
+		&self,
+		texture: &TextureInfo,
+		layer: u32,
+		level: u8,
+		reader: impl Read + Seek,
+	) -> Result<Vec<u8>, G1tReadError> {
+		let format = texture.format()?;
+		let (width, height) = texture.mipmap_dimensions(level);
+		let data = self.read_layer_raw(texture, layer, level, reader)?;
+
+		Ok(dds_decoder::decode_image(
+			format,
+			&data,
+			width as usize,
+			height as usize,
+		)?)
+	}
+
+	/// Writes a texture to a `.dds` file, including all mipmap levels and layers. Compressed data is
+	/// copied as-is, without re-encoding it.
+	pub fn write_dds(
+		&self,
+		texture: &TextureInfo,
+		mut reader: impl Read + Seek,
+		writer: impl Write,
+	) -> Result<(), G1tWriteError> {
+		let format = texture.format()?;
+
+		let mut data = vec![];
+		for layer in 0..texture.layer_count() {
+			for level in 0..texture.header.mipmaps {
+				data.extend(self.read_layer_raw(texture, layer, level, &mut reader)?);
+			}
+		}
+
+		let description = dds_decoder::dds::DdsDescription::new(
+			format,
+			texture.width,
+			texture.height,
+			texture.header.mipmaps as u32,
+			texture.frames,
+			texture.is_cubemap(),
+		);
+		dds_decoder::dds::write_dds(writer, &description, &data)?;
+
+		Ok(())
+	}
+
+	/// Writes a texture to a `.ktx2` file, including all mipmap levels, frames and cubemap faces.
+	/// Compressed data is copied as-is, without re-encoding it.
+	pub fn write_ktx2(
+		&self,
+		texture: &TextureInfo,
+		mut reader: impl Read + Seek,
+		writer: impl Write,
+	) -> Result<(), G1tWriteError> {
+		let format = texture.format()?;
+
+		// ktx2 stores all layers per mipmap level, while g1t stores all mipmap levels per layer
+		let levels = (0..texture.header.mipmaps)
+			.map(|level| {
+				let mut data = vec![];
+				for layer in 0..texture.layer_count() {
+					data.extend(self.read_layer_raw(texture, layer, level, &mut reader)?);
+				}
+				Ok(data)
+			})
+			.collect::<Result<Vec<_>, G1tWriteError>>()?;
+
+		let ktx2 = ktx2::Ktx2Texture {
+			format,
+			width: texture.width,
+			height: texture.height,
+			layer_count: if texture.frames > 1 {
+				texture.frames
+			} else {
+				0
+			},
+			face_count: if texture.is_cubemap() { 6 } else { 1 },
+			levels,
+		};
+		ktx2.write(writer)?;
+
+		Ok(())
+	}
+
+	/// Reads a single mipmap level of a single layer of a texture, without decoding it. The data is
+	/// in the format returned by [TextureInfo::format], in the linear layout used on Windows.
+	///
+	/// See [TextureInfo::layer_count] for the order of the layers.
+	pub fn read_layer_raw(
+		&self,
+		texture: &TextureInfo,
+		layer: u32,
+		level: u8,
+		mut reader: impl Read + Seek,
+	) -> Result<Vec<u8>, G1tReadError> {
+		if level >= texture.header.mipmaps {
+			return Err(G1tReadError::InvalidMipmapLevel(
+				level,
+				texture.header.mipmaps,
+			));
+		}
+
+		if layer >= texture.layer_count() {
+			return Err(G1tReadError::InvalidLayer(layer, texture.layer_count()));
+		}
+
 		if texture.header.z_mipmaps > 0 {
 			// TODO: allow reading z-mipmaps
 			tracing::debug!(
@@ -145,107 +493,159 @@ impl GustG1t {
 			);
 		}
 
-		if texture.frames > 1 {
-			return Err(G1tReadError::Unimplemented(
-				format!(
-					"Texture has {} frames, only 1 is supported for now",
-					texture.frames
-				)
-				.into(),
-			));
+		let format = texture.format()?;
+		let platform = self.header.platform;
+		let (width, height) = texture.mipmap_dimensions(level);
+		let too_large = || G1tReadError::TextureTooLarge(texture.width, texture.height);
+		let data_size = swizzle::stored_size(platform, format, width as usize, height as usize)
+			.ok_or_else(too_large)?;
+		let data_offset = texture
+			.layer_size(platform, format)
+			.and_then(|layer_size| layer_size.checked_mul(layer as u64))
+			.and_then(|offset| offset.checked_add(texture.mipmap_offset(platform, format, level)?))
+			.and_then(|offset| offset.checked_add(texture.absolute_data_offset))
+			.ok_or_else(too_large)?;
+		debug!(
+			layer,
+			level, width, height, data_offset, data_size, "Reading mipmap"
+		);
+
+		// check this up front, so a truncated file doesn't show up as a generic IO error
+		let file_size = util::stream_len(&mut reader)?;
+		let data_end = data_offset
+			.checked_add(data_size as u64)
+			.ok_or_else(too_large)?;
+		if data_end > file_size {
+			return Err(G1tReadError::TruncatedTextureData(data_end, file_size));
 		}
 
-		// TODO: deduplicate this code
-		match texture_type_to_dds_format(texture.header.texture_type) {
-			Some(dds_decoder::DdsFormat::RGBA8) => {
-				// assuming mipmap level 0
-				let data_size = texture.width as usize * texture.height as usize * 4;
-				debug!(?data_size, "Size of image data");
+		reader.seek(std::io::SeekFrom::Start(data_offset))?;
 
-				reader.seek(std::io::SeekFrom::Start(texture.absolute_data_offset))?;
+		let mut data = vec![0u8; data_size];
+		reader.read_exact(&mut data)?;
+		debug!(len = data.len(), "Data read");
 
-				let mut data = vec![0u8; data_size];
-				reader.read_exact(&mut data)?;
-				debug!(len = data.len(), "Data read");
+		Ok(swizzle::unswizzle(
+			platform,
+			format,
+			data,
+			width as usize,
+			height as usize,
+		))
+	}
+}
 
-				Ok(data)
-			}
-			Some(dds_decoder::DdsFormat::BC1) => {
-				// assuming mipmap level 0
-				let blocks_x = usize::max(1, (texture.width as usize + 3) / 4);
-				let blocks_y = usize::max(1, (texture.height as usize + 3) / 4);
-				let encoded_data_size = blocks_x * blocks_y * 8;
-				debug!(?encoded_data_size, "Size of encoded image data");
-
-				reader.seek(std::io::SeekFrom::Start(texture.absolute_data_offset))?;
-
-				let mut data = vec![0u8; encoded_data_size];
-				reader.read_exact(&mut data)?;
-				debug!(len = data.len(), "Data read");
-
-				Ok(dds_decoder::decode_image(
-					dds_decoder::DdsFormat::BC1,
-					&data,
-					texture.width as usize,
-					texture.height as usize,
-				)?)
-			}
-			Some(dds_decoder::DdsFormat::BC3) => {
-				// assuming mipmap level 0
-				let blocks_x = usize::max(1, (texture.width as usize + 3) / 4);
-				let blocks_y = usize::max(1, (texture.height as usize + 3) / 4);
-				let encoded_data_size = blocks_x * blocks_y * 16;
-				debug!(?encoded_data_size, "Size of encoded image data");
-
-				reader.seek(std::io::SeekFrom::Start(texture.absolute_data_offset))?;
-
-				let mut data = vec![0u8; encoded_data_size];
-				reader.read_exact(&mut data)?;
-				debug!(len = data.len(), "Data read");
-
-				Ok(dds_decoder::decode_image(
-					dds_decoder::DdsFormat::BC3,
-					&data,
-					texture.width as usize,
-					texture.height as usize,
-				)?)
-			}
-			Some(dds_decoder::DdsFormat::BC7) => {
-				// assuming mipmap level 0
-				let blocks_x = usize::max(1, (texture.width as usize + 3) / 4);
-				let blocks_y = usize::max(1, (texture.height as usize + 3) / 4);
-				let encoded_data_size = blocks_x * blocks_y * 16;
-				debug!(?encoded_data_size, "Size of encoded image data");
-
-				reader.seek(std::io::SeekFrom::Start(texture.absolute_data_offset))?;
-
-				let mut data = vec![0u8; encoded_data_size];
-				reader.read_exact(&mut data)?;
-				debug!(len = data.len(), "Data read");
-
-				Ok(dds_decoder::decode_image(
-					dds_decoder::DdsFormat::BC7,
-					&data,
-					texture.width as usize,
-					texture.height as usize,
-				)?)
-			}
-			Some(x) => Err(G1tReadError::Unimplemented(
-				format!("DDS format {:?} is not yet supported", x).into(),
-			)),
-			None => Err(G1tReadError::Unimplemented(
+impl TextureInfo {
+	/// Returns the size of the texture header, including the extended data.
+	fn header_size(&self) -> u32 {
+		if self.header.flags.contains(TextureFlags::EXTENDED_DATA) {
+			G1tTextureHeader::SIZE + 4 + self.extended_data.len() as u32 * 4
+		} else {
+			G1tTextureHeader::SIZE
+		}
+	}
+
+	/// Returns the format the texture data is stored in.
+	pub fn format(&self) -> Result<dds_decoder::DdsFormat, G1tReadError> {
+		texture_type_to_dds_format(self.header.texture_type).ok_or_else(|| {
+			G1tReadError::Unimplemented(
 				format!(
 					"texture format 0x{:02X} is not yet supported",
-					texture.header.texture_type
+					self.header.texture_type
 				)
 				.into(),
-			)),
+			)
+		})
+	}
+
+	/// Returns the width and height of a mipmap level. Level 0 is the full-size image.
+	pub fn mipmap_dimensions(&self, level: u8) -> (u32, u32) {
+		(
+			u32::max(1, self.width >> level),
+			u32::max(1, self.height >> level),
+		)
+	}
+
+	/// Returns the offset of a mipmap level relative to the start of the texture data. The levels
+	/// are stored back to back, starting with the largest.
+	///
+	/// Returns `None` if the offset does not fit in a `u64`.
+	fn mipmap_offset(
+		&self,
+		platform: Platform,
+		format: dds_decoder::DdsFormat,
+		level: u8,
+	) -> Option<u64> {
+		(0..level).try_fold(0u64, |offset, level| {
+			let (width, height) = self.mipmap_dimensions(level);
+			let size = swizzle::stored_size(platform, format, width as usize, height as usize)?;
+			offset.checked_add(size as u64)
+		})
+	}
+
+	/// Returns the size of a single layer, including all its mipmap levels.
+	///
+	/// Returns `None` if the size does not fit in a `u64`.
+	fn layer_size(&self, platform: Platform, format: dds_decoder::DdsFormat) -> Option<u64> {
+		self.mipmap_offset(platform, format, self.header.mipmaps)
+	}
+
+	/// Returns whether the texture data holds 6 layers per frame, which marks a cubemap. Padding
+	/// after the data is allowed, as long as it is smaller than a layer.
+	fn has_cubemap_size(&self, platform: Platform) -> bool {
+		let Ok(format) = self.format() else {
+			return false;
+		};
+		let Some(layer_size) = self.layer_size(platform, format) else {
+			return false;
+		};
+		layer_size > 0 && self.data_size / layer_size == self.frames as u64 * 6
+	}
+
+	/// Returns whether this texture is a cubemap, see [TextureInfo::cubemap].
+	pub fn is_cubemap(&self) -> bool {
+		self.cubemap
+	}
+
+	/// Returns whether this texture is marked as a normal map.
+	pub fn is_normal_map(&self) -> bool {
+		self.global_flags.contains(GlobalTextureFlags::NORMAL_MAP)
+	}
+
+	/// Returns the amount of images stored in this texture, not counting mipmaps.
+	///
+	/// This is the amount of frames, times 6 for cubemaps. The layers are stored one after the
+	/// other, each with its own mipmap chain. Cubemap faces are stored per frame in the order +X,
+	/// -X, +Y, -Y, +Z, -Z, like in DDS files.
+	pub fn layer_count(&self) -> u32 {
+		if self.is_cubemap() {
+			self.frames * 6
+		} else {
+			self.frames
 		}
 	}
 }
 
+/// A single decoded RGBA8 image, such as one mipmap level of a texture.
+pub struct TextureImage {
+	pub width: u32,
+	pub height: u32,
+	pub data: Vec<u8>,
+}
+
+impl TextureImage {
+	/// Rebuilds the blue channel of a normal map, which is not stored for formats such as BC5.
+	///
+	/// See [TextureInfo::is_normal_map].
+	pub fn reconstruct_normal_z(&mut self) {
+		dds_decoder::reconstruct_normal_z(&mut self.data);
+	}
+}
+
 #[derive(Debug)]
 pub struct G1tHeader {
+	/// The byte order of the file. Files for older consoles are stored as big-endian.
+	pub endian: scroll::Endian,
 	pub version: u16,
 	pub header_size: u32,
 	pub texture_count: u32,
@@ -256,21 +656,24 @@ pub struct G1tHeader {
 impl G1tHeader {
 	const MAGIC_LITTLE_ENDIAN: u32 = u32::from_be_bytes(*b"G1TG");
 	const MAGIC_BIG_ENDIAN: u32 = u32::from_le_bytes(*b"G1TG");
+	/// The size of the fixed part of the header, before the global texture flags.
+	const SIZE: u32 = 0x1C;
 
 	fn read(mut reader: impl Read + Seek) -> Result<(Self, Vec<GlobalTextureFlags>), G1tReadError> {
-		let magic = reader.ioread()?;
-		let version_string: u32 = reader.ioread()?;
-		let total_size: u32 = reader.ioread()?;
-		let header_size = reader.ioread()?;
-		let texture_count = reader.ioread()?;
-		let platform: u32 = reader.ioread()?;
-		let extra_size = reader.ioread()?;
-
-		match magic {
-			Self::MAGIC_LITTLE_ENDIAN => {}
-			Self::MAGIC_BIG_ENDIAN => todo!("support big endian g1t files"),
+		let magic = reader.ioread_with(scroll::LE)?;
+		let endian = match magic {
+			Self::MAGIC_LITTLE_ENDIAN => scroll::LE,
+			Self::MAGIC_BIG_ENDIAN => scroll::BE,
 			_ => return Err(G1tReadError::InvalidHeaderMagic(magic)),
-		}
+		};
+		trace!(?endian);
+
+		let version_string: u32 = reader.ioread_with(endian)?;
+		let total_size: u32 = reader.ioread_with(endian)?;
+		let header_size = reader.ioread_with(endian)?;
+		let texture_count = reader.ioread_with(endian)?;
+		let platform: u32 = reader.ioread_with(endian)?;
+		let extra_size = reader.ioread_with(endian)?;
 
 		// the version is ascii, presumably decimal
 		// some known values:
@@ -295,7 +698,7 @@ impl G1tHeader {
 		}
 
 		let platform = Platform::from_repr(platform as usize)
-			.ok_or_else(|| G1tReadError::UnknownPlatform(platform))?;
+			.ok_or(G1tReadError::UnknownPlatform(platform))?;
 
 		if extra_size > 0xFFFF || extra_size % 4 != 0 {
 			return Err(G1tReadError::InvalidExtraSize(extra_size));
@@ -304,7 +707,7 @@ impl G1tHeader {
 		let global_flags = (0..texture_count)
 			.map(|_| {
 				reader
-					.ioread::<u32>()
+					.ioread_with::<u32>(endian)
 					.map(GlobalTextureFlags::from_bits_retain)
 			})
 			.collect::<Result<Vec<_>, _>>()?;
@@ -312,6 +715,7 @@ impl G1tHeader {
 
 		Ok((
 			Self {
+				endian,
 				version,
 				header_size,
 				texture_count,
@@ -321,6 +725,37 @@ impl G1tHeader {
 			global_flags,
 		))
 	}
+
+	/// Writes the header, without the global texture flags.
+	fn write(&self, mut writer: impl Write, total_size: u32) -> std::io::Result<()> {
+		let magic = if self.endian.is_little() {
+			Self::MAGIC_LITTLE_ENDIAN
+		} else {
+			Self::MAGIC_BIG_ENDIAN
+		};
+		writer.iowrite_with(magic, scroll::LE)?;
+
+		let version_string = format!("{:04}", self.version);
+		let version_string = u32::from_be_bytes(
+			version_string
+				.as_bytes()
+				.try_into()
+				.expect("version has 4 digits"),
+		);
+
+		for value in [
+			version_string,
+			total_size,
+			self.header_size,
+			self.texture_count,
+			self.platform as u32,
+			self.extra_size,
+		] {
+			writer.iowrite_with(value, self.endian)?;
+		}
+
+		Ok(())
+	}
 }
 
 #[derive(Debug)]
@@ -332,10 +767,14 @@ pub struct G1tTextureHeader {
 	dx: u8,
 	/// Y size, as a power of 2
 	dy: u8,
-	flags: TextureFlags,
+	pub flags: TextureFlags,
 }
 
 impl G1tTextureHeader {
+	const SIZE: u32 = 8;
+
+	/// Reads the texture header. It only consists of single bytes, so it is the same for both
+	/// little-endian and big-endian files.
 	fn read(mut reader: impl Read + Seek) -> Result<Self, G1tReadError> {
 		let packed_mipmaps: u8 = reader.ioread()?;
 		let r#type = reader.ioread()?;
@@ -364,6 +803,16 @@ impl G1tTextureHeader {
 		})
 	}
 
+	/// Writes the texture header, without the extended data.
+	fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+		let packed_mipmaps = (self.mipmaps << 4) | (self.z_mipmaps & 0x0F);
+		let packed_dimensions = (self.dy << 4) | (self.dx & 0x0F);
+		writer.write_all(&[packed_mipmaps, self.texture_type, packed_dimensions])?;
+		writer.write_all(&self.flags.bits().to_be_bytes()[3..8])?;
+
+		Ok(())
+	}
+
 	pub fn width(&self) -> u32 {
 		1 << self.dx
 	}
@@ -379,6 +828,8 @@ fn texture_type_to_dds_format(texture_type: u8) -> Option<dds_decoder::DdsFormat
 		0x01 | 0x02 => Some(dds_decoder::DdsFormat::RGBA8),
 		0x06 | 0x59 => Some(dds_decoder::DdsFormat::BC1),
 		0x08 | 0x5B => Some(dds_decoder::DdsFormat::BC3),
+		0x5C => Some(dds_decoder::DdsFormat::BC4),
+		0x5D => Some(dds_decoder::DdsFormat::BC5),
 		0x5E => Some(dds_decoder::DdsFormat::BC6H),
 		0x5F | 0x72 => Some(dds_decoder::DdsFormat::BC7),
 		_ => None,
@@ -386,15 +837,24 @@ fn texture_type_to_dds_format(texture_type: u8) -> Option<dds_decoder::DdsFormat
 }
 
 bitflags::bitflags! {
-	#[derive(Debug, Copy, Clone)]
-	struct GlobalTextureFlags: u32 {
+	/// Flags stored for each texture in the file header.
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub struct GlobalTextureFlags: u32 {
 		const NORMAL_MAP = 0x00_00_03;
 	}
 }
 
+impl GlobalTextureFlags {
+	/// Returns the bits that don't match any known flag.
+	pub fn unknown_bits(&self) -> u32 {
+		self.bits() & !Self::all().bits()
+	}
+}
+
 bitflags::bitflags! {
-	#[derive(Debug, Copy, Clone)]
-	struct TextureFlags: u64 {
+	/// Flags stored in the texture header.
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub struct TextureFlags: u64 {
 		// NOTE: gust_tools shifts all nibbles of the texture flags around! we don't do this
 
 		const STANDARD_FLAGS = 0x00_00_10_21_00;
@@ -404,6 +864,13 @@ bitflags::bitflags! {
 	}
 }
 
+impl TextureFlags {
+	/// Returns the bits that don't match any known flag.
+	pub fn unknown_bits(&self) -> u64 {
+		self.bits() & !Self::all().bits()
+	}
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, strum::FromRepr)]
 pub enum Platform {
 	/// Sony PlayStation 2
@@ -436,3 +903,620 @@ pub enum Platform {
 	/// Nintendo Switch
 	Switch = 0x10,
 }
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::*;
+
+	/// Builds a little-endian g1t file containing a single texture.
+	fn build_g1t(texture_type: u8, mipmaps: u8, dx: u8, dy: u8, data: &[u8]) -> Vec<u8> {
+		build_g1t_ext(texture_type, mipmaps, dx, dy, 0, &[], data)
+	}
+
+	/// Builds a little-endian g1t file containing a single texture with the given texture flags
+	/// and extended data. The size of the extended data is prepended automatically.
+	fn build_g1t_ext(
+		texture_type: u8,
+		mipmaps: u8,
+		dx: u8,
+		dy: u8,
+		flags: u64,
+		extended_data: &[u32],
+		data: &[u8],
+	) -> Vec<u8> {
+		build_g1t_endian(
+			scroll::LE,
+			Platform::Windows,
+			texture_type,
+			mipmaps,
+			dx,
+			dy,
+			flags,
+			extended_data,
+			data,
+		)
+	}
+
+	/// Builds a g1t file like [build_g1t_ext], with the given byte order and platform.
+	#[allow(clippy::too_many_arguments)]
+	fn build_g1t_endian(
+		endian: scroll::Endian,
+		platform: Platform,
+		texture_type: u8,
+		mipmaps: u8,
+		dx: u8,
+		dy: u8,
+		flags: u64,
+		extended_data: &[u32],
+		data: &[u8],
+	) -> Vec<u8> {
+		const HEADER_SIZE: u32 = 0x1C + 4;
+
+		let to_bytes = |value: u32| {
+			if endian.is_little() {
+				value.to_le_bytes()
+			} else {
+				value.to_be_bytes()
+			}
+		};
+
+		let mut flags = flags;
+		let mut extended_data_bytes = vec![];
+		if !extended_data.is_empty() {
+			flags |= TextureFlags::EXTENDED_DATA.bits();
+			let extended_data_len = (extended_data.len() as u32 + 1) * 4;
+			for value in std::iter::once(extended_data_len).chain(extended_data.iter().copied()) {
+				extended_data_bytes.extend_from_slice(&to_bytes(value));
+			}
+		}
+
+		let mut file = vec![];
+		if endian.is_little() {
+			file.extend_from_slice(b"GT1G0600");
+		} else {
+			file.extend_from_slice(b"G1TG0060");
+		}
+		let total_size = HEADER_SIZE + 4 + 8 + (extended_data_bytes.len() + data.len()) as u32;
+		for value in [total_size, HEADER_SIZE, 1, platform as u32, 0, 0] {
+			file.extend_from_slice(&to_bytes(value));
+		}
+
+		// offset table
+		file.extend_from_slice(&to_bytes(4));
+
+		// texture header
+		file.extend_from_slice(&[mipmaps << 4, texture_type, dx | (dy << 4)]);
+		file.extend_from_slice(&flags.to_be_bytes()[3..8]);
+		file.extend_from_slice(&extended_data_bytes);
+		file.extend_from_slice(data);
+
+		assert_eq!(file.len(), total_size as usize);
+		file
+	}
+
+	/// Creates a BC1 block where every pixel has the given color.
+	fn solid_bc1_block(color: u16) -> [u8; 8] {
+		let [lo, hi] = color.to_le_bytes();
+		[lo, hi, lo, hi, 0, 0, 0, 0]
+	}
+
+	#[test]
+	fn read_rgba8_mipmaps() {
+		// 8x4 image with 4 levels: 8x4, 4x2, 2x1, 1x1
+		let level_sizes = [8 * 4, 4 * 2, 2, 1];
+		let data = level_sizes
+			.iter()
+			.enumerate()
+			.flat_map(|(level, &pixels)| vec![level as u8; pixels * 4])
+			.collect::<Vec<_>>();
+		let file = build_g1t(0x01, 4, 3, 2, &data);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let texture = &g1t.textures[0];
+
+		let mipmaps = g1t.read_mipmaps(texture, &mut reader).unwrap();
+		assert_eq!(mipmaps.len(), 4);
+		for (level, mipmap) in mipmaps.iter().enumerate() {
+			assert_eq!(
+				(mipmap.width, mipmap.height),
+				texture.mipmap_dimensions(level as u8)
+			);
+			assert_eq!(mipmap.data, vec![level as u8; level_sizes[level] * 4]);
+		}
+		assert_eq!((mipmaps[3].width, mipmaps[3].height), (1, 1));
+	}
+
+	#[test]
+	fn read_bc1_mipmaps() {
+		// 8x8 image with 3 levels: 4 blocks, 1 block and 1 padded block
+		let colors = [0xF800, 0x07E0, 0x001F];
+		let mut data = vec![];
+		data.extend((0..4).flat_map(|_| solid_bc1_block(colors[0])));
+		data.extend(solid_bc1_block(colors[1]));
+		data.extend(solid_bc1_block(colors[2]));
+		let file = build_g1t(0x59, 3, 3, 3, &data);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let texture = &g1t.textures[0];
+
+		let expected = [[248, 0, 0, 255], [0, 252, 0, 255], [0, 0, 248, 255]];
+		for level in 0..3 {
+			let image = g1t.read_mipmap(texture, level, &mut reader).unwrap();
+			let (width, height) = texture.mipmap_dimensions(level);
+			assert_eq!(image.len(), (width * height * 4) as usize);
+			assert!(image
+				.chunks_exact(4)
+				.all(|pixel| pixel == expected[level as usize]));
+		}
+
+		assert!(matches!(
+			g1t.read_mipmap(texture, 3, &mut reader),
+			Err(G1tReadError::InvalidMipmapLevel(3, 3))
+		));
+	}
+
+	#[test]
+	fn read_truncated_texture_data() {
+		// 4x4 BC1 image with 2 levels, where the second level is cut off. the total size in the
+		// header is updated to match, like in a badly extracted file.
+		let mut data = vec![];
+		data.extend(solid_bc1_block(0xF800));
+		data.extend(solid_bc1_block(0x07E0));
+		let mut file = build_g1t(0x59, 2, 2, 2, &data);
+		file.truncate(file.len() - 4);
+		let total_size = file.len() as u32;
+		file[8..12].copy_from_slice(&total_size.to_le_bytes());
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let texture = &g1t.textures[0];
+
+		assert!(g1t.read_image(texture, &mut reader).is_ok());
+		assert!(matches!(
+			g1t.read_mipmap(texture, 1, &mut reader),
+			Err(G1tReadError::TruncatedTextureData(end, size)) if end == size + 4
+		));
+	}
+
+	#[test]
+	fn reject_huge_dimensions() {
+		let file = build_g1t_ext(0x01, 1, 1, 1, 0, &[0, 0, u32::MAX, u32::MAX], &[0; 4]);
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		assert!(matches!(
+			g1t.read_image(&g1t.textures[0], &mut reader),
+			Err(G1tReadError::TextureTooLarge(u32::MAX, u32::MAX))
+		));
+	}
+
+	#[test]
+	fn reject_sections_past_end_of_file() {
+		let file = build_g1t(0x01, 1, 0, 0, &[0; 4]);
+
+		// the header claims to be larger than the whole file
+		let mut corrupt = file.clone();
+		corrupt[0x0C..0x10].copy_from_slice(&0x7FFF_FFF0u32.to_le_bytes());
+		assert!(matches!(
+			GustG1t::read(Cursor::new(corrupt)),
+			Err(G1tReadError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+		));
+
+		// the texture starts past the end of the file
+		let mut corrupt = file.clone();
+		corrupt[0x20..0x24].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(matches!(
+			GustG1t::read(Cursor::new(corrupt)),
+			Err(G1tReadError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+		));
+	}
+
+	#[test]
+	fn read_rgba8_frames() {
+		// 2x2 image with 2 levels and 3 frames, each frame has its own mipmap chain
+		let data = (0..3u8)
+			.flat_map(|frame| {
+				let mut layer = vec![frame * 2; 2 * 2 * 4];
+				layer.extend([frame * 2 + 1; 4]);
+				layer
+			})
+			.collect::<Vec<_>>();
+		let file = build_g1t_ext(0x01, 2, 1, 1, 0, &[0, 3 << 28], &data);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let texture = &g1t.textures[0];
+		assert_eq!(texture.frames, 3);
+		assert_eq!(texture.layer_count(), 3);
+		assert!(!texture.is_cubemap());
+
+		let layers = g1t.read_layers(texture, &mut reader).unwrap();
+		assert_eq!(layers.len(), 3);
+		for (frame, layer) in layers.iter().enumerate() {
+			assert_eq!(layer.data, vec![frame as u8 * 2; 2 * 2 * 4]);
+		}
+
+		for frame in 0..3u32 {
+			let mipmap = g1t.read_layer(texture, frame, 1, &mut reader).unwrap();
+			assert_eq!(mipmap, vec![frame as u8 * 2 + 1; 4]);
+		}
+
+		assert!(matches!(
+			g1t.read_layer(texture, 3, 0, &mut reader),
+			Err(G1tReadError::InvalidLayer(3, 3))
+		));
+	}
+
+	#[test]
+	fn read_cubemap_faces() {
+		let data = (0..6u8)
+			.flat_map(|face| vec![face; 4 * 4 * 4])
+			.collect::<Vec<_>>();
+		let file = build_g1t_ext(0x01, 1, 2, 2, 0, &[], &data);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let texture = &g1t.textures[0];
+		assert!(texture.is_cubemap());
+		assert_eq!(texture.layer_count(), 6);
+
+		let faces = g1t.read_layers(texture, &mut reader).unwrap();
+		for (face, image) in faces.iter().enumerate() {
+			assert_eq!(image.data, vec![face as u8; 4 * 4 * 4]);
+		}
+	}
+
+	#[test]
+	fn detect_cubemaps_from_data_size() {
+		let layer = [0; 4 * 4 * 4];
+
+		// 2 frames with 6 faces each
+		let file = build_g1t_ext(0x01, 1, 2, 2, 0, &[0, 2 << 28], &layer.repeat(12));
+		let g1t = GustG1t::read(Cursor::new(file)).unwrap();
+		assert!(g1t.textures[0].is_cubemap());
+		assert_eq!(g1t.textures[0].layer_count(), 12);
+
+		// padding smaller than a layer is ignored
+		let data = [layer.repeat(6), vec![0; 16]].concat();
+		let file = build_g1t_ext(0x01, 1, 2, 2, 0, &[], &data);
+		let g1t = GustG1t::read(Cursor::new(file)).unwrap();
+		assert!(g1t.textures[0].is_cubemap());
+
+		for layers in [1, 5, 7] {
+			let file = build_g1t_ext(0x01, 1, 2, 2, 0, &[], &layer.repeat(layers));
+			let g1t = GustG1t::read(Cursor::new(file)).unwrap();
+			assert!(!g1t.textures[0].is_cubemap());
+		}
+
+		// the guess can be overridden
+		let file = build_g1t_ext(0x01, 1, 2, 2, 0, &[], &layer.repeat(6));
+		let mut g1t = GustG1t::read(Cursor::new(file)).unwrap();
+		g1t.textures[0].cubemap = false;
+		assert_eq!(g1t.textures[0].layer_count(), 1);
+	}
+
+	#[test]
+	fn read_big_endian_header() {
+		let data = vec![0x7F; 4 * 4 * 4];
+		let file = build_g1t_endian(
+			scroll::BE,
+			Platform::PlayStation3,
+			0x01,
+			1,
+			2,
+			2,
+			0,
+			&[],
+			&data,
+		);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		assert_eq!(g1t.header.endian, scroll::BE);
+		assert_eq!(g1t.header.version, 60);
+		assert_eq!(g1t.header.texture_count, 1);
+		assert_eq!(g1t.header.platform, Platform::PlayStation3);
+
+		let texture = &g1t.textures[0];
+		assert_eq!((texture.width, texture.height), (4, 4));
+		assert_eq!(g1t.read_image(texture, &mut reader).unwrap(), data);
+	}
+
+	#[test]
+	fn read_big_endian_extended_data() {
+		// 3 frames with a non-standard size of 3x5
+		let data = (0..3u8)
+			.flat_map(|frame| vec![frame; 3 * 5 * 4])
+			.collect::<Vec<_>>();
+		let file = build_g1t_endian(
+			scroll::BE,
+			Platform::PlayStation3,
+			0x01,
+			1,
+			2,
+			3,
+			0,
+			&[0, 3 << 28, 3, 5],
+			&data,
+		);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let texture = &g1t.textures[0];
+		assert_eq!((texture.width, texture.height), (3, 5));
+		assert_eq!(texture.frames, 3);
+
+		let layers = g1t.read_layers(texture, &mut reader).unwrap();
+		for (frame, layer) in layers.iter().enumerate() {
+			assert_eq!(layer.data, vec![frame as u8; 3 * 5 * 4]);
+		}
+	}
+
+	#[test]
+	fn read_little_endian_header() {
+		let file = build_g1t(0x01, 1, 0, 0, &[0; 4]);
+		let g1t = GustG1t::read(Cursor::new(file)).unwrap();
+		assert_eq!(g1t.header.endian, scroll::LE);
+		assert_eq!(g1t.header.version, 60);
+	}
+
+	#[test]
+	fn reject_invalid_magic() {
+		let mut file = build_g1t(0x01, 1, 0, 0, &[0; 4]);
+		file[0..4].copy_from_slice(b"DDS ");
+		assert!(matches!(
+			GustG1t::read(Cursor::new(file)),
+			Err(G1tReadError::InvalidHeaderMagic(_))
+		));
+	}
+
+	#[test]
+	fn read_unswizzles_console_textures() {
+		// 8x8 RGBA8 image on PS4 is a single tile in morton order
+		let mut data = vec![0u8; 8 * 8 * 4];
+		for y in 0..8u8 {
+			for x in 0..8u8 {
+				let index = swizzle::morton(x as usize, y as usize) * 4;
+				data[index..index + 4].copy_from_slice(&[x, y, 0, 255]);
+			}
+		}
+		let file = build_g1t_endian(
+			scroll::LE,
+			Platform::PlayStation4,
+			0x01,
+			1,
+			3,
+			3,
+			0,
+			&[],
+			&data,
+		);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let image = g1t.read_image(&g1t.textures[0], &mut reader).unwrap();
+		for (i, pixel) in image.chunks_exact(4).enumerate() {
+			assert_eq!(pixel, [(i % 8) as u8, (i / 8) as u8, 0, 255]);
+		}
+	}
+
+	#[test]
+	fn unverified_texture_types_are_unsupported() {
+		for texture_type in [0x00, 0x0D, 0x0F, 0x34, 0x36] {
+			let file = build_g1t(texture_type, 1, 1, 0, &[0; 4]);
+			let g1t = GustG1t::read(Cursor::new(file)).unwrap();
+			assert!(matches!(
+				g1t.textures[0].format(),
+				Err(G1tReadError::Unimplemented(_))
+			));
+		}
+	}
+
+	#[test]
+	fn write_dds_keeps_layers_and_mipmaps() {
+		// 8x8 BC1 cubemap with 2 levels, every face has its own color
+		let colors = [0xF800, 0x07E0, 0x001F, 0xFFFF, 0x0000, 0x8410];
+		let data = colors
+			.iter()
+			.flat_map(|&color| solid_bc1_block(color).repeat(4 + 1))
+			.collect::<Vec<_>>();
+		let file = build_g1t_ext(0x59, 2, 3, 3, 0, &[], &data);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let mut dds = vec![];
+		g1t.write_dds(&g1t.textures[0], &mut reader, &mut dds)
+			.unwrap();
+
+		let dds = dds_decoder::dds::DdsFile::parse(&dds).unwrap();
+		assert_eq!(dds.description.format, dds_decoder::DdsFormat::BC1);
+		assert_eq!(dds.description.mipmap_count, 2);
+		assert!(dds.description.is_cubemap);
+		assert_eq!(dds.description.array_size, 1);
+		for (face, color) in colors.iter().enumerate() {
+			assert_eq!(
+				dds.surface_data(face as u32, 0).unwrap(),
+				solid_bc1_block(*color).repeat(4)
+			);
+			assert_eq!(
+				dds.surface_data(face as u32, 1).unwrap(),
+				solid_bc1_block(*color)
+			);
+		}
+	}
+
+	#[test]
+	fn write_dds_unknown_texture_type() {
+		let file = build_g1t_ext(0x7F, 1, 1, 1, 0, &[], &[0; 8]);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		assert!(matches!(
+			g1t.write_dds(&g1t.textures[0], &mut reader, vec![]),
+			Err(G1tWriteError::ReadError(G1tReadError::Unimplemented(_)))
+		));
+	}
+
+	#[test]
+	fn write_ktx2_unknown_texture_type() {
+		let file = build_g1t_ext(0x7F, 1, 1, 1, 0, &[], &[0; 8]);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		assert!(matches!(
+			g1t.write_ktx2(&g1t.textures[0], &mut reader, vec![]),
+			Err(G1tWriteError::ReadError(G1tReadError::Unimplemented(_)))
+		));
+	}
+
+	#[test]
+	fn write_ktx2_orders_layers_per_level() {
+		// 2x2 RGBA8 image with 2 levels and 2 frames
+		let data = (0..2u8)
+			.flat_map(|frame| {
+				let mut layer = vec![frame * 2; 2 * 2 * 4];
+				layer.extend([frame * 2 + 1; 4]);
+				layer
+			})
+			.collect::<Vec<_>>();
+		let file = build_g1t_ext(0x01, 2, 1, 1, 0, &[0, 2 << 28], &data);
+
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let mut output = vec![];
+		g1t.write_ktx2(&g1t.textures[0], &mut reader, &mut output)
+			.unwrap();
+
+		let ktx2 = ::ktx2::Reader::new(&output).unwrap();
+		assert_eq!(ktx2.header().format, Some(::ktx2::Format::R8G8B8A8_UNORM));
+		assert_eq!(ktx2.header().layer_count, 2);
+		assert_eq!(ktx2.header().face_count, 1);
+
+		let levels = ktx2.levels().collect::<Vec<_>>();
+		assert_eq!(levels.len(), 2);
+		assert_eq!(levels[0].data, [vec![0; 16], vec![2; 16]].concat());
+		assert_eq!(levels[1].data, [1, 1, 1, 1, 3, 3, 3, 3]);
+	}
+
+	/// Reads a g1t file and writes it back with the original texture data.
+	fn write_unchanged(file: &[u8]) -> Vec<u8> {
+		let mut reader = Cursor::new(file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let texture_data = g1t
+			.textures
+			.iter()
+			.map(|texture| g1t.read_texture_data(texture, &mut reader).unwrap())
+			.collect::<Vec<_>>();
+
+		let mut written = vec![];
+		g1t.write(&texture_data, &mut written).unwrap();
+		written
+	}
+
+	#[test]
+	fn write_round_trip() {
+		let data = (0..4 * 4 * 4 + 2 * 2 * 4 + 4)
+			.map(|i| i as u8)
+			.collect::<Vec<_>>();
+		let file = build_g1t(0x01, 3, 2, 2, &data);
+		assert_eq!(write_unchanged(&file), file);
+
+		let data = (0..3 * 3 * 5 * 4).map(|i| i as u8).collect::<Vec<_>>();
+		let file = build_g1t_endian(
+			scroll::BE,
+			Platform::PlayStation3,
+			0x01,
+			1,
+			2,
+			3,
+			0,
+			&[0, 3 << 28, 3, 5],
+			&data,
+		);
+		assert_eq!(write_unchanged(&file), file);
+	}
+
+	#[test]
+	fn write_round_trip_keeps_texture_order() {
+		// 2 1x1 RGBA8 textures, where the second texture is stored first and is followed by padding
+		let mut file = vec![];
+		file.extend_from_slice(b"GT1G0600");
+		let header_size = 0x1C + 2 * 4;
+		let total_size = header_size + 2 * 4 + (8 + 4 + 4) + (8 + 4);
+		for value in [
+			total_size,
+			header_size,
+			2,
+			Platform::Windows as u32,
+			0,
+			0,
+			0,
+		] {
+			file.extend_from_slice(&value.to_le_bytes());
+		}
+		for offset in [8 + 16, 8u32] {
+			file.extend_from_slice(&offset.to_le_bytes());
+		}
+		file.extend_from_slice(&[0x10, 0x01, 0x00, 0, 0, 0, 0, 0]);
+		file.extend_from_slice(&[1, 2, 3, 4, 0xAA, 0xAA, 0xAA, 0xAA]);
+		file.extend_from_slice(&[0x10, 0x01, 0x00, 0, 0, 0, 0, 0]);
+		file.extend_from_slice(&[5, 6, 7, 8]);
+		assert_eq!(file.len(), total_size as usize);
+
+		let mut reader = Cursor::new(&file);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let image = g1t.read_image(&g1t.textures[0], &mut reader).unwrap();
+		assert_eq!(image, [5, 6, 7, 8]);
+		let data = g1t
+			.read_texture_data(&g1t.textures[1], &mut reader)
+			.unwrap();
+		assert_eq!(data, [1, 2, 3, 4, 0xAA, 0xAA, 0xAA, 0xAA]);
+
+		assert_eq!(write_unchanged(&file), file);
+	}
+
+	#[test]
+	fn write_replaced_texture_data() {
+		let file = build_g1t(0x59, 1, 2, 2, &solid_bc1_block(0xF800));
+		let g1t = GustG1t::read(Cursor::new(&file)).unwrap();
+
+		let mut written = vec![];
+		g1t.write(&[solid_bc1_block(0x001F)], &mut written).unwrap();
+		assert_eq!(written.len(), file.len());
+
+		let mut reader = Cursor::new(written);
+		let g1t = GustG1t::read(&mut reader).unwrap();
+		let image = g1t.read_image(&g1t.textures[0], &mut reader).unwrap();
+		assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 0, 248, 255]));
+
+		assert!(matches!(
+			g1t.write(&[[0u8; 8]; 2], &mut vec![]),
+			Err(G1tWriteError::TextureCountMismatch(1, 2))
+		));
+	}
+
+	#[test]
+	fn read_extra_data_and_unknown_flags() {
+		let mut file = build_g1t_ext(0x01, 1, 0, 0, 0x00_00_00_02_00, &[], &[1, 2, 3, 4]);
+		file.splice(0x24..0x24, [0x78, 0x56, 0x34, 0x12, 7, 0, 0, 0]);
+		let total_size = file.len() as u32;
+		file[0x08..0x0C].copy_from_slice(&total_size.to_le_bytes());
+		file[0x18..0x1C].copy_from_slice(&8u32.to_le_bytes());
+		file[0x1C..0x20].copy_from_slice(&0x103u32.to_le_bytes());
+		file[0x20..0x24].copy_from_slice(&12u32.to_le_bytes());
+
+		let g1t = GustG1t::read(Cursor::new(&file)).unwrap();
+		assert_eq!(g1t.header.extra_size, 8);
+		assert_eq!(g1t.extra_data, [0x12345678, 7]);
+
+		let texture = &g1t.textures[0];
+		assert!(texture.is_normal_map());
+		assert_eq!(texture.global_flags.unknown_bits(), 0x100);
+		assert_eq!(texture.header.flags.unknown_bits(), 0x00_00_00_02_00);
+
+		assert_eq!(write_unchanged(&file), file);
+	}
+}
@@ -0,0 +1,420 @@
+//! Console GPUs store textures in tiled layouts, which have to be converted back to the linear
+//! layout used on Windows before they can be decoded.
+//!
+//! All layouts work on elements: 4x4 blocks for block-compressed formats, or single pixels for
+//! uncompressed formats.
+
+use dds_decoder::DdsFormat;
+
+use crate::Platform;
+
+/// Returns the amount of bytes an image takes up in the layout used by the given platform. This
+/// can be larger than the linear size, since tiled layouts are padded.
+///
+/// Returns `None` if the size does not fit in a `usize`.
+pub fn stored_size(
+	platform: Platform,
+	format: DdsFormat,
+	width: usize,
+	height: usize,
+) -> Option<usize> {
+	let (elements_x, elements_y) = element_dimensions(format, width, height);
+	let element_size = format.block_size();
+
+	let (padded_x, padded_y) = match platform {
+		Platform::PlayStation4 => (
+			elements_x.checked_next_multiple_of(8)?,
+			elements_y.checked_next_multiple_of(8)?,
+		),
+		Platform::PlayStationVita => (
+			elements_x.checked_next_power_of_two()?,
+			elements_y.checked_next_power_of_two()?,
+		),
+		Platform::Switch => {
+			let block_height = switch_block_height(elements_y);
+			let width_in_gobs = elements_x.checked_mul(element_size)?.div_ceil(GOB_WIDTH);
+			return width_in_gobs
+				.checked_mul(GOB_WIDTH)?
+				.checked_mul(elements_y.checked_next_multiple_of(GOB_HEIGHT * block_height)?);
+		}
+		_ => (elements_x, elements_y),
+	};
+	padded_x.checked_mul(padded_y)?.checked_mul(element_size)
+}
+
+/// Converts an image from the layout used by the given platform to a linear layout.
+///
+/// The input should be [stored_size] bytes long, the output is [DdsFormat::data_size] bytes long.
+/// The sizes are expected to fit in a `usize`, as checked by [stored_size].
+/// Platforms that already use a linear layout return the data as-is.
+pub fn unswizzle(
+	platform: Platform,
+	format: DdsFormat,
+	data: Vec<u8>,
+	width: usize,
+	height: usize,
+) -> Vec<u8> {
+	let (elements_x, elements_y) = element_dimensions(format, width, height);
+	let element_size = format.block_size();
+
+	match platform {
+		Platform::PlayStation4 => {
+			let tiles_x = elements_x.div_ceil(8);
+			unswizzle_with(&data, elements_x, elements_y, element_size, |x, y| {
+				ps4_address(x, y, tiles_x) * element_size
+			})
+		}
+		Platform::PlayStationVita => {
+			let (padded_x, padded_y) = (
+				elements_x.next_power_of_two(),
+				elements_y.next_power_of_two(),
+			);
+			unswizzle_with(&data, elements_x, elements_y, element_size, |x, y| {
+				vita_address(x, y, padded_x, padded_y) * element_size
+			})
+		}
+		Platform::Switch => {
+			let block_height = switch_block_height(elements_y);
+			let width_in_gobs = (elements_x * element_size).div_ceil(GOB_WIDTH);
+			unswizzle_with(&data, elements_x, elements_y, element_size, |x, y| {
+				switch_address(x * element_size, y, width_in_gobs, block_height)
+			})
+		}
+		_ => data,
+	}
+}
+
+fn element_dimensions(format: DdsFormat, width: usize, height: usize) -> (usize, usize) {
+	if format.is_block_compressed() {
+		(
+			usize::max(1, width.div_ceil(4)),
+			usize::max(1, height.div_ceil(4)),
+		)
+	} else {
+		(width, height)
+	}
+}
+
+/// Copies every element from its tiled byte offset, as returned by `address`, to its linear
+/// position. Elements that lie outside of the data are left as zeroes.
+fn unswizzle_with(
+	data: &[u8],
+	elements_x: usize,
+	elements_y: usize,
+	element_size: usize,
+	address: impl Fn(usize, usize) -> usize,
+) -> Vec<u8> {
+	let mut output = vec![0u8; elements_x * elements_y * element_size];
+	for y in 0..elements_y {
+		for x in 0..elements_x {
+			let source = address(x, y);
+			let Some(element) = data.get(source..source + element_size) else {
+				continue;
+			};
+
+			let target = (y * elements_x + x) * element_size;
+			output[target..target + element_size].copy_from_slice(element);
+		}
+	}
+	output
+}
+
+/// Interleaves the bits of x and y, with x in the even bits and y in the odd bits.
+pub fn morton(x: usize, y: usize) -> usize {
+	let mut result = 0;
+	for bit in 0..usize::BITS as usize / 2 {
+		result |= ((x >> bit) & 1) << (2 * bit);
+		result |= ((y >> bit) & 1) << (2 * bit + 1);
+	}
+	result
+}
+
+/// The PS4 GNM tiling mode used for textures stores 8x8 element tiles in row-major order. The
+/// elements within a tile are stored in Morton order.
+fn ps4_address(x: usize, y: usize, tiles_x: usize) -> usize {
+	let tile = (y / 8) * tiles_x + x / 8;
+	tile * 64 + morton(x % 8, y % 8)
+}
+
+/// The Vita stores textures in Morton order. For non-square textures, the square with the size of
+/// the shortest side is repeated along the longest side.
+fn vita_address(x: usize, y: usize, width: usize, height: usize) -> usize {
+	let square_size = usize::min(width, height);
+	let square_bits = square_size.trailing_zeros();
+	let mask = square_size - 1;
+
+	let square = if width > height {
+		x >> square_bits
+	} else {
+		y >> square_bits
+	};
+	(square << (2 * square_bits)) | morton(x & mask, y & mask)
+}
+
+/// The width of a Switch GOB (group of bytes) in bytes.
+const GOB_WIDTH: usize = 64;
+/// The height of a Switch GOB in rows.
+const GOB_HEIGHT: usize = 8;
+const GOB_SIZE: usize = GOB_WIDTH * GOB_HEIGHT;
+
+/// Returns the amount of GOBs stacked vertically in a block, based on the height in elements.
+fn switch_block_height(elements_y: usize) -> usize {
+	elements_y
+		.div_ceil(GOB_HEIGHT)
+		.clamp(1, 16)
+		.next_power_of_two()
+}
+
+/// The Switch uses the block-linear layout of Tegra GPUs. The image is divided into blocks of
+/// `block_height` GOBs stacked vertically, which are stored in row-major order. The bytes within
+/// a GOB are stored in a fixed pattern of 16 byte rows.
+fn switch_address(x: usize, y: usize, width_in_gobs: usize, block_height: usize) -> usize {
+	let block_rows = GOB_HEIGHT * block_height;
+	let gob_address = (y / block_rows) * width_in_gobs * GOB_SIZE * block_height
+		+ (x / GOB_WIDTH) * GOB_SIZE * block_height
+		+ ((y % block_rows) / GOB_HEIGHT) * GOB_SIZE;
+
+	let (x, y) = (x % GOB_WIDTH, y % GOB_HEIGHT);
+	gob_address + (x / 32) * 256 + (y / 2) * 64 + ((x % 32) / 16) * 32 + (y % 2) * 16 + x % 16
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Creates an RGBA8 image where every pixel contains its own coordinates.
+	fn coordinate_image(width: usize, height: usize) -> Vec<u8> {
+		(0..height)
+			.flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 0, 255]))
+			.collect()
+	}
+
+	/// Creates RGBA8 data where every pixel contains its own index as a little-endian u32.
+	fn indexed_pixels(count: usize) -> Vec<u8> {
+		(0..count as u32).flat_map(u32::to_le_bytes).collect()
+	}
+
+	/// Reads back the indices written by [indexed_pixels], in linear order.
+	fn pixel_indices(data: &[u8]) -> Vec<u32> {
+		data.chunks_exact(4)
+			.map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+			.collect()
+	}
+
+	#[test]
+	fn morton_interleaves_bits() {
+		assert_eq!(morton(0, 0), 0);
+		assert_eq!(morton(1, 0), 1);
+		assert_eq!(morton(0, 1), 2);
+		assert_eq!(morton(1, 1), 3);
+		assert_eq!(morton(2, 0), 4);
+		assert_eq!(morton(7, 7), 63);
+	}
+
+	#[test]
+	fn linear_platforms_are_unchanged() {
+		let image = coordinate_image(4, 4);
+		let result = unswizzle(Platform::Windows, DdsFormat::RGBA8, image.clone(), 4, 4);
+		assert_eq!(result, image);
+	}
+
+	#[test]
+	fn unswizzle_ps4_tiles() {
+		// a 16x8 image consists of 2 tiles
+		let mut tiled = vec![];
+		for tile_x in 0..2 {
+			for i in 0..64 {
+				// undo the morton order
+				let (x, y) = (0..8)
+					.flat_map(|y| (0..8).map(move |x| (x, y)))
+					.find(|&(x, y)| morton(x, y) == i)
+					.unwrap();
+				tiled.extend([(tile_x * 8 + x) as u8, y as u8, 0, 255]);
+			}
+		}
+
+		assert_eq!(
+			stored_size(Platform::PlayStation4, DdsFormat::RGBA8, 16, 8),
+			Some(tiled.len())
+		);
+		let result = unswizzle(Platform::PlayStation4, DdsFormat::RGBA8, tiled, 16, 8);
+		assert_eq!(result, coordinate_image(16, 8));
+	}
+
+	#[test]
+	fn unswizzle_ps4_known_answer() {
+		// the standard 8x8 Z-order table, the second tile follows the first one
+		#[rustfmt::skip]
+		let expected = [
+			 0,  1,  4,  5, 16, 17, 20, 21,  64,  65,  68,  69,  80,  81,  84,  85,
+			 2,  3,  6,  7, 18, 19, 22, 23,  66,  67,  70,  71,  82,  83,  86,  87,
+			 8,  9, 12, 13, 24, 25, 28, 29,  72,  73,  76,  77,  88,  89,  92,  93,
+			10, 11, 14, 15, 26, 27, 30, 31,  74,  75,  78,  79,  90,  91,  94,  95,
+			32, 33, 36, 37, 48, 49, 52, 53,  96,  97, 100, 101, 112, 113, 116, 117,
+			34, 35, 38, 39, 50, 51, 54, 55,  98,  99, 102, 103, 114, 115, 118, 119,
+			40, 41, 44, 45, 56, 57, 60, 61, 104, 105, 108, 109, 120, 121, 124, 125,
+			42, 43, 46, 47, 58, 59, 62, 63, 106, 107, 110, 111, 122, 123, 126, 127,
+		];
+
+		let tiled = indexed_pixels(16 * 8);
+		let result = unswizzle(Platform::PlayStation4, DdsFormat::RGBA8, tiled, 16, 8);
+		assert_eq!(pixel_indices(&result), expected);
+	}
+
+	#[test]
+	fn unswizzle_ps4_pads_to_tiles() {
+		// a 8x8 BC1 image is a single padded tile of 2x2 blocks
+		assert_eq!(
+			stored_size(Platform::PlayStation4, DdsFormat::BC1, 8, 8),
+			Some(64 * 8)
+		);
+
+		let mut tiled = vec![0u8; 64 * 8];
+		for (i, block) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter().enumerate() {
+			tiled[morton(block.0, block.1) * 8] = i as u8 + 1;
+		}
+		let result = unswizzle(Platform::PlayStation4, DdsFormat::BC1, tiled, 8, 8);
+		assert_eq!(result.len(), 4 * 8);
+		assert_eq!([result[0], result[8], result[16], result[24]], [1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn unswizzle_vita_morton() {
+		// 4x2 image: two 2x2 squares next to each other
+		let swizzled = [
+			(0, 0),
+			(1, 0),
+			(0, 1),
+			(1, 1),
+			(2, 0),
+			(3, 0),
+			(2, 1),
+			(3, 1),
+		]
+		.iter()
+		.flat_map(|&(x, y)| [x, y, 0, 255])
+		.collect::<Vec<_>>();
+		let result = unswizzle(Platform::PlayStationVita, DdsFormat::RGBA8, swizzled, 4, 2);
+		assert_eq!(result, coordinate_image(4, 2));
+
+		// 2x4 image: two 2x2 squares on top of each other
+		let swizzled = [
+			(0, 0),
+			(1, 0),
+			(0, 1),
+			(1, 1),
+			(0, 2),
+			(1, 2),
+			(0, 3),
+			(1, 3),
+		]
+		.iter()
+		.flat_map(|&(x, y)| [x, y, 0, 255])
+		.collect::<Vec<_>>();
+		let result = unswizzle(Platform::PlayStationVita, DdsFormat::RGBA8, swizzled, 2, 4);
+		assert_eq!(result, coordinate_image(2, 4));
+	}
+
+	#[test]
+	fn unswizzle_vita_known_answer() {
+		// 8x4 image: the 4x4 Z-order table, repeated for the second square
+		#[rustfmt::skip]
+		let expected = [
+			 0,  1,  4,  5, 16, 17, 20, 21,
+			 2,  3,  6,  7, 18, 19, 22, 23,
+			 8,  9, 12, 13, 24, 25, 28, 29,
+			10, 11, 14, 15, 26, 27, 30, 31,
+		];
+
+		let swizzled = indexed_pixels(8 * 4);
+		let result = unswizzle(Platform::PlayStationVita, DdsFormat::RGBA8, swizzled, 8, 4);
+		assert_eq!(pixel_indices(&result), expected);
+	}
+
+	#[test]
+	fn stored_size_overflow() {
+		for platform in [
+			Platform::Windows,
+			Platform::PlayStation4,
+			Platform::PlayStationVita,
+			Platform::Switch,
+		] {
+			assert_eq!(stored_size(platform, DdsFormat::RGBA8, usize::MAX, 2), None);
+			assert_eq!(
+				stored_size(platform, DdsFormat::BC1, usize::MAX / 2, usize::MAX / 2),
+				None
+			);
+		}
+	}
+
+	#[test]
+	fn switch_gob_layout() {
+		// the first 16 bytes of each row are stored in pairs of rows
+		assert_eq!(switch_address(0, 0, 1, 1), 0);
+		assert_eq!(switch_address(0, 1, 1, 1), 16);
+		assert_eq!(switch_address(16, 0, 1, 1), 32);
+		assert_eq!(switch_address(0, 2, 1, 1), 64);
+		assert_eq!(switch_address(32, 0, 1, 1), 256);
+		assert_eq!(switch_address(63, 7, 1, 1), 511);
+
+		// the next GOB down in the same block comes right after the first one
+		assert_eq!(switch_address(0, 8, 2, 2), 512);
+		// the next block to the right comes after all GOBs of the block
+		assert_eq!(switch_address(64, 0, 2, 2), 1024);
+		// the next row of blocks comes after all blocks in the row
+		assert_eq!(switch_address(0, 16, 2, 2), 2048);
+	}
+
+	#[test]
+	fn unswizzle_switch_known_answer() {
+		// 8x8 RGBA8 fills the left half of a single GOB. Its 16 byte sectors are stored in the
+		// order given by the GOB layout table of the Tegra X1 TRM, as also used by Ryujinx:
+		//   row 0: 0, 32, 256, 288    row 1: 16, 48, 272, 304
+		//   row 2: 64, 96, 320, 352   row 3: 80, 112, 336, 368
+		//   row 4: 128, 160, ...      row 5: 144, 176, ...
+		//   row 6: 192, 224, ...      row 7: 208, 240, ...
+		#[rustfmt::skip]
+		let expected = [
+			 0,  1,  2,  3,  8,  9, 10, 11,
+			 4,  5,  6,  7, 12, 13, 14, 15,
+			16, 17, 18, 19, 24, 25, 26, 27,
+			20, 21, 22, 23, 28, 29, 30, 31,
+			32, 33, 34, 35, 40, 41, 42, 43,
+			36, 37, 38, 39, 44, 45, 46, 47,
+			48, 49, 50, 51, 56, 57, 58, 59,
+			52, 53, 54, 55, 60, 61, 62, 63,
+		];
+
+		assert_eq!(
+			stored_size(Platform::Switch, DdsFormat::RGBA8, 8, 8),
+			Some(GOB_SIZE)
+		);
+		let swizzled = indexed_pixels(GOB_SIZE / 4);
+		let result = unswizzle(Platform::Switch, DdsFormat::RGBA8, swizzled, 8, 8);
+		assert_eq!(pixel_indices(&result), expected);
+	}
+
+	#[test]
+	fn unswizzle_switch_block_linear() {
+		// 16x16 RGBA8 is 64 bytes wide, so 1 GOB wide and 2 GOBs high
+		let (width, height) = (16, 16);
+		assert_eq!(switch_block_height(height), 2);
+		assert_eq!(
+			stored_size(Platform::Switch, DdsFormat::RGBA8, width, height),
+			Some(2 * GOB_SIZE)
+		);
+
+		let linear = coordinate_image(width, height);
+		let mut swizzled = vec![0u8; 2 * GOB_SIZE];
+		for y in 0..height {
+			for x in 0..width {
+				let source = (y * width + x) * 4;
+				let target = switch_address(x * 4, y, 1, 2);
+				swizzled[target..target + 4].copy_from_slice(&linear[source..source + 4]);
+			}
+		}
+
+		let result = unswizzle(Platform::Switch, DdsFormat::RGBA8, swizzled, width, height);
+		assert_eq!(result, linear);
+	}
+}
@@ -1,4 +1,4 @@
-use std::io::{Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
 
 /// `Seek::stream_len` taken from the standard library, where this function is currently unstable.
 ///
@@ -15,3 +15,18 @@ pub fn stream_len<T: Seek>(stream: &mut T) -> std::io::Result<u64> {
 
 	Ok(len)
 }
+
+/// Reads everything from the current position up to `end`. Nothing is read if the stream is
+/// already past `end`, and an `UnexpectedEof` error is returned if the stream ends before `end`.
+pub fn read_until<T: Read + Seek>(stream: &mut T, end: u64) -> std::io::Result<Vec<u8>> {
+	let position = stream.stream_position()?;
+	let len = end.saturating_sub(position);
+
+	// `end` comes from the file, so only allocate as much as can actually be read
+	let mut data = vec![];
+	stream.take(len).read_to_end(&mut data)?;
+	if (data.len() as u64) < len {
+		return Err(std::io::ErrorKind::UnexpectedEof.into());
+	}
+	Ok(data)
+}
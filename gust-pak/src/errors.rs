@@ -15,4 +15,33 @@ pub enum PakReadError {
 	InvalidHeaderSize(u32),
 	#[error("Too many files: {0} (max 65536)")]
 	TooManyFiles(u32),
+	#[error("Could not detect the game version")]
+	UnknownGameVersion,
+}
+
+#[derive(Error, Debug)]
+pub enum PakWriteError {
+	#[error("IO error: {0}")]
+	IoError(#[from] std::io::Error),
+
+	#[error("File name is not ascii: {0}")]
+	FileNameNotAscii(String),
+	#[error("Invalid file name: {0} (must be shorter than 128 bytes and not contain null bytes)")]
+	InvalidFileName(String),
+	#[error("File {0} is too large: {1} bytes (max 4 GiB)")]
+	FileTooLarge(String, u64),
+	#[error("File {0} changed size while writing: expected {1} bytes but wrote {2}")]
+	FileSizeChanged(String, u64, u64),
+	#[error("Too many files: {0} (max 65536)")]
+	TooManyFiles(usize),
+	#[error("Data offset {0:#x} does not fit in a 32-bit entry")]
+	DataOffsetTooLarge(u64),
+}
+
+#[derive(Error, Debug)]
+pub enum PakFilterError {
+	#[error("Invalid glob pattern `{0}`: {1}")]
+	InvalidGlob(String, glob::PatternError),
+	#[error("Invalid regex: {0}")]
+	InvalidRegex(#[from] regex::Error),
 }
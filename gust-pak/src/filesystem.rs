@@ -0,0 +1,365 @@
+use std::{
+	collections::BTreeMap,
+	fs::File,
+	io::{self, BufReader, Read, Seek},
+	path::{Path, PathBuf},
+};
+
+use gust_common::GameVersion;
+use tracing::{debug, trace};
+
+use crate::{errors::PakReadError, normalize_path, GustPak, PakEntryRef};
+
+/// A read-only view over all .pak files of a game, as if their contents were a single directory.
+///
+/// Files are looked up by their normalized path (see [PakEntryRef::get_normalized_file_name]),
+/// ignoring case. Paths can either be full paths like `Data/x64/res_cmn/ui/a24_item_icon.g1t`, or
+/// relative to the directory that all files share, like `res_cmn/ui/a24_item_icon.g1t`.
+///
+/// When multiple .pak files contain the same file, the one that was added last wins.
+#[derive(Default)]
+pub struct GameFileSystem {
+	paks: Vec<GamePak>,
+	/// The location of every file, keyed by its lowercase normalized path.
+	files: BTreeMap<String, FileLocation>,
+	/// The lowercase directory that all files share, with a trailing `/`. `None` until the first
+	/// file is added.
+	data_root: Option<String>,
+}
+
+struct GamePak {
+	path: PathBuf,
+	game_version: GameVersion,
+	pak: GustPak,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FileLocation {
+	pak_index: usize,
+	entry_index: usize,
+}
+
+impl GameFileSystem {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Indexes every .pak file in a directory, like the `Data` directory of a game.
+	///
+	/// The files are added in order of their file names, so `PACK01.PAK` overrides `PACK00.PAK`.
+	/// If no game version is given, it is detected for each .pak file.
+	pub fn open(
+		data_dir: impl AsRef<Path>,
+		game_version: Option<GameVersion>,
+	) -> Result<Self, PakReadError> {
+		let mut pak_paths = vec![];
+		for entry in std::fs::read_dir(data_dir)? {
+			let path = entry?.path();
+			if path.is_file()
+				&& path
+					.extension()
+					.is_some_and(|ext| ext.eq_ignore_ascii_case("pak"))
+			{
+				pak_paths.push(path);
+			}
+		}
+		pak_paths.sort_by_key(|path| path.file_name().map(|name| name.to_ascii_lowercase()));
+
+		let mut file_system = Self::new();
+		for path in pak_paths {
+			file_system.add_pak(path, game_version)?;
+		}
+		Ok(file_system)
+	}
+
+	/// Indexes a .pak file. Its files override files with the same path in previously added .pak
+	/// files.
+	///
+	/// If no game version is given, it is detected from the file.
+	pub fn add_pak(
+		&mut self,
+		path: impl Into<PathBuf>,
+		game_version: Option<GameVersion>,
+	) -> Result<(), PakReadError> {
+		let path = path.into();
+		debug!("Adding {:?} to the file system", path);
+
+		let mut reader = BufReader::new(File::open(&path)?);
+		let game_version = match game_version {
+			Some(game_version) => game_version,
+			None => GustPak::detect_game_version(&mut reader)?,
+		};
+		let pak = GustPak::read_index(&mut reader, game_version)?;
+
+		let pak_index = self.paks.len();
+		for (entry_index, entry) in pak.entries.iter().enumerate() {
+			let key = entry.get_normalized_file_name().to_ascii_lowercase();
+
+			let directory = key.rfind('/').map_or("", |end| &key[..=end]);
+			let data_root = match self.data_root.take() {
+				Some(data_root) => common_directory(&data_root, directory).to_owned(),
+				None => directory.to_owned(),
+			};
+			self.data_root = Some(data_root);
+
+			let location = FileLocation {
+				pak_index,
+				entry_index,
+			};
+			if let Some(previous) = self.files.insert(key, location) {
+				trace!(
+					"{} overrides the file from {:?}",
+					entry.get_file_name(),
+					self.paks[previous.pak_index].path
+				);
+			}
+		}
+
+		self.paks.push(GamePak {
+			path,
+			game_version,
+			pak,
+		});
+		Ok(())
+	}
+
+	/// The amount of distinct files in all .pak files.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.files.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.files.is_empty()
+	}
+
+	/// Finds the file that a path resolves to.
+	pub fn resolve(&self, path: &str) -> Option<GameFile<'_>> {
+		let path = normalize_path(path);
+		let location = self
+			.files
+			.get(&path)
+			.or_else(|| self.files.get(&format!("{}{path}", self.data_root())))?;
+
+		let pak = &self.paks[location.pak_index];
+		Some(GameFile {
+			pak_path: &pak.path,
+			game_version: pak.game_version,
+			entry: pak
+				.pak
+				.entries
+				.get(location.entry_index)
+				.expect("indexed entry should exist"),
+			pak: &pak.pak,
+		})
+	}
+
+	/// Opens a reader for the unencrypted data of the file that a path resolves to.
+	pub fn open_file(&self, path: &str) -> io::Result<impl Read + Seek> {
+		let file = self.resolve(path).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				format!("{path} is not in any .pak file"),
+			)
+		})?;
+		file.open()
+	}
+
+	/// Lists the files and directories directly inside a directory, sorted by name.
+	///
+	/// Returns an empty list if the directory does not exist.
+	pub fn list_dir(&self, path: &str) -> Vec<DirEntry> {
+		let path = normalize_path(path);
+		let entries = self.list_normalized_dir(&path);
+		if !entries.is_empty() || self.data_root().is_empty() {
+			return entries;
+		}
+
+		let path = format!("{}{path}", self.data_root());
+		self.list_normalized_dir(path.trim_end_matches('/'))
+	}
+
+	fn list_normalized_dir(&self, path: &str) -> Vec<DirEntry> {
+		let prefix = if path.is_empty() {
+			String::new()
+		} else {
+			format!("{path}/")
+		};
+
+		let mut entries: Vec<DirEntry> = vec![];
+		for (key, location) in self.files.range(prefix.clone()..) {
+			if !key.starts_with(&prefix) {
+				break;
+			}
+
+			// use the original casing of the winning entry
+			let file_name = self.paks[location.pak_index]
+				.pak
+				.entries
+				.get(location.entry_index)
+				.expect("indexed entry should exist")
+				.get_normalized_file_name();
+			let name = &file_name[prefix.len()..];
+
+			let entry = match name.split_once('/') {
+				Some((directory, _)) => DirEntry::Directory(directory.to_owned()),
+				None => DirEntry::File(name.to_owned()),
+			};
+
+			// files in the same directory are next to each other in the map
+			let is_duplicate = matches!(
+				(entries.last(), &entry),
+				(Some(DirEntry::Directory(previous)), DirEntry::Directory(directory))
+					if previous.eq_ignore_ascii_case(directory)
+			);
+			if !is_duplicate {
+				entries.push(entry);
+			}
+		}
+		entries
+	}
+
+	/// The lowercase directory that all files share, with a trailing `/`.
+	fn data_root(&self) -> &str {
+		self.data_root.as_deref().unwrap_or_default()
+	}
+}
+
+/// A file in a [GameFileSystem].
+#[derive(Clone, Copy)]
+pub struct GameFile<'fs> {
+	/// The path of the .pak file that holds this file.
+	pub pak_path: &'fs Path,
+	pub game_version: GameVersion,
+	pub entry: PakEntryRef<'fs>,
+	pak: &'fs GustPak,
+}
+
+impl GameFile<'_> {
+	/// Opens a reader for the file's unencrypted data.
+	pub fn open(&self) -> io::Result<impl Read + Seek> {
+		let file = File::open(self.pak_path)?;
+		self.entry.get_reader(file, self.pak, self.game_version)
+	}
+}
+
+/// An item in a directory of a [GameFileSystem].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirEntry {
+	File(String),
+	Directory(String),
+}
+
+/// Returns the longest directory that contains both directories. Both have a trailing `/`.
+fn common_directory<'a>(a: &'a str, b: &str) -> &'a str {
+	let common_len = a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count();
+	a[..common_len].rfind('/').map_or("", |end| &a[..=end])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::GustPakWriter;
+
+	fn write_pak(path: &Path, files: &[(&str, &[u8])]) {
+		let mut writer = GustPakWriter::new(GameVersion::A24);
+		for (file_name, data) in files {
+			writer.add_file(*file_name, data.to_vec()).unwrap();
+		}
+		writer.write(File::create(path).unwrap()).unwrap();
+	}
+
+	fn read_file(file_system: &GameFileSystem, path: &str) -> Vec<u8> {
+		let mut data = vec![];
+		file_system
+			.open_file(path)
+			.unwrap()
+			.read_to_end(&mut data)
+			.unwrap();
+		data
+	}
+
+	#[test]
+	fn later_paks_override_earlier_ones() {
+		let root = std::env::temp_dir().join(format!("gust-pak-filesystem-{}", std::process::id()));
+		std::fs::create_dir_all(&root).unwrap();
+		write_pak(
+			&root.join("PACK01.PAK"),
+			&[(r"\Data\x64\res_cmn\ui\a24_item_icon.g1t", b"new")],
+		);
+		write_pak(
+			&root.join("PACK00.PAK"),
+			&[
+				(r"\Data\x64\res_cmn\ui\a24_item_icon.g1t", b"old"),
+				(r"\Data\x64\res_cmn\ui\a24_item_icon_2.g1t", b"2"),
+				(r"\Data\x64\res_cmn\model\pc01.g1m", b"model"),
+				(r"\Data\x64\event.bin", b"event"),
+			],
+		);
+		std::fs::write(root.join("readme.txt"), b"not a pak").unwrap();
+
+		let file_system = GameFileSystem::open(&root, None).unwrap();
+
+		assert_eq!(file_system.len(), 4);
+		let file = file_system.resolve("res_cmn/ui/a24_item_icon.g1t").unwrap();
+		assert_eq!(file.pak_path.file_name().unwrap(), "PACK01.PAK");
+		assert_eq!(file.game_version, GameVersion::A24);
+		assert_eq!(file.entry.get_file_size(), 3);
+
+		assert_eq!(
+			read_file(&file_system, "res_cmn/ui/a24_item_icon.g1t"),
+			b"new"
+		);
+		assert!(file_system
+			.resolve(r"\DATA\X64\RES_CMN\UI\A24_ITEM_ICON_2.G1T")
+			.is_some());
+		assert!(file_system.resolve("ui/a24_item_icon.g1t").is_none());
+
+		assert_eq!(
+			file_system.list_dir("res_cmn"),
+			[
+				DirEntry::Directory("model".to_owned()),
+				DirEntry::Directory("ui".to_owned()),
+			]
+		);
+		assert_eq!(
+			file_system.list_dir("/Data/x64/"),
+			[
+				DirEntry::File("event.bin".to_owned()),
+				DirEntry::Directory("res_cmn".to_owned()),
+			]
+		);
+		assert_eq!(
+			file_system.list_dir(""),
+			[DirEntry::Directory("Data".to_owned())]
+		);
+		assert!(file_system.list_dir("missing").is_empty());
+
+		std::fs::remove_dir_all(&root).unwrap();
+	}
+
+	#[test]
+	fn open_file_reads_winning_entry() {
+		let root =
+			std::env::temp_dir().join(format!("gust-pak-filesystem-open-{}", std::process::id()));
+		std::fs::create_dir_all(&root).unwrap();
+		write_pak(&root.join("a.pak"), &[(r"\Data\file.bin", b"old")]);
+		write_pak(&root.join("b.pak"), &[(r"\Data\file.bin", b"new")]);
+
+		let mut file_system = GameFileSystem::new();
+		file_system
+			.add_pak(root.join("b.pak"), Some(GameVersion::A24))
+			.unwrap();
+		file_system
+			.add_pak(root.join("a.pak"), Some(GameVersion::A24))
+			.unwrap();
+
+		assert_eq!(read_file(&file_system, "file.bin"), b"old");
+		assert_eq!(read_file(&file_system, "Data/file.bin"), b"old");
+		let error = file_system.open_file("other.bin").err().unwrap();
+		assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+		std::fs::remove_dir_all(&root).unwrap();
+	}
+}
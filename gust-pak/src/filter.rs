@@ -0,0 +1,73 @@
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+
+use crate::{errors::PakFilterError, PakEntryRef};
+
+/// A pattern that is matched against the normalized file name of an entry, as returned by
+/// [PakEntryRef::get_normalized_file_name].
+#[derive(Debug, Clone)]
+pub enum EntryPattern {
+	/// A glob pattern that has to match the whole file name, ignoring case. `*` also matches `/`,
+	/// so `*.g1t` matches every .g1t file in any directory.
+	Glob(Pattern),
+	/// A regex that has to match any part of the file name, ignoring case.
+	Regex(Regex),
+}
+
+impl EntryPattern {
+	/// Parses a glob pattern, like `Data/x64/res_cmn/ui/*`.
+	pub fn glob(pattern: &str) -> Result<Self, PakFilterError> {
+		Pattern::new(pattern)
+			.map(Self::Glob)
+			.map_err(|e| PakFilterError::InvalidGlob(pattern.to_owned(), e))
+	}
+
+	/// Parses a regex, like `\.g1t$`.
+	pub fn regex(pattern: &str) -> Result<Self, PakFilterError> {
+		let regex = RegexBuilder::new(pattern).case_insensitive(true).build()?;
+		Ok(Self::Regex(regex))
+	}
+
+	/// Checks if a normalized file name matches this pattern.
+	pub fn matches(&self, file_name: &str) -> bool {
+		match self {
+			EntryPattern::Glob(pattern) => pattern.matches_with(
+				file_name,
+				MatchOptions {
+					case_sensitive: false,
+					require_literal_separator: false,
+					require_literal_leading_dot: false,
+				},
+			),
+			EntryPattern::Regex(regex) => regex.is_match(file_name),
+		}
+	}
+}
+
+/// Selects entries of a .pak file by their file name.
+///
+/// An entry is selected if it matches any of the `include` patterns, or if there are none, and it
+/// does not match any of the `exclude` patterns.
+#[derive(Debug, Clone, Default)]
+pub struct PakEntryFilter {
+	pub include: Vec<EntryPattern>,
+	pub exclude: Vec<EntryPattern>,
+}
+
+impl PakEntryFilter {
+	/// Checks if an entry is selected by this filter.
+	pub fn matches(&self, entry: &PakEntryRef<'_>) -> bool {
+		let file_name = entry.get_normalized_file_name();
+
+		let included = self.include.is_empty()
+			|| self
+				.include
+				.iter()
+				.any(|pattern| pattern.matches(&file_name));
+		included
+			&& !self
+				.exclude
+				.iter()
+				.any(|pattern| pattern.matches(&file_name))
+	}
+}
@@ -1,19 +1,32 @@
 use errors::PakReadError;
-use gust_common::GameVersion;
+use gust_common::{strum::IntoEnumIterator, GameVersion};
 use scroll::IOread;
 use std::{
+	collections::HashMap,
 	ffi::CStr,
 	io::{self, Read, Seek},
+	sync::OnceLock,
 };
 use tracing::{debug, trace, warn};
 use utils::XorReader;
 
+pub use filesystem::{DirEntry, GameFile, GameFileSystem};
+pub use filter::{EntryPattern, PakEntryFilter};
 pub use gust_common as common;
+#[cfg(feature = "mmap")]
+pub use mapped::MappedGustPak;
+pub use utils::SliceXorReader;
+pub use writer::GustPakWriter;
 
 use crate::utils::FencedReader;
 
-mod errors;
+pub mod errors;
+mod filesystem;
+mod filter;
+#[cfg(feature = "mmap")]
+mod mapped;
 mod utils;
+mod writer;
 
 /// A representation of the contents of a .pak file. This does not include the file data itself, but
 /// can be used to read the file data.
@@ -26,6 +39,9 @@ pub struct GustPak {
 
 	/// the offset at which the entries begin.
 	data_start: u64,
+
+	/// Maps lowercase normalized file names to entry indices, built on the first lookup.
+	index: OnceLock<HashMap<String, usize>>,
 }
 
 impl GustPak {
@@ -95,6 +111,7 @@ impl GustPak {
 			header,
 			entries,
 			data_start,
+			index: OnceLock::new(),
 		})
 	}
 
@@ -102,6 +119,106 @@ impl GustPak {
 		self.data_start
 	}
 
+	/// Finds an entry by its path, ignoring case. Both `\` and `/` are accepted as separators, and
+	/// the leading separator is optional.
+	///
+	/// The first lookup builds an index of all entries, so later lookups don't scan the entries.
+	/// Changes to [Self::entries] after that are not seen by the index.
+	pub fn find(&self, path: &str) -> Option<PakEntryRef<'_>> {
+		let index = self.index.get_or_init(|| {
+			let mut index = HashMap::with_capacity(self.entries.len());
+			for (i, entry) in self.entries.iter().enumerate() {
+				let key = entry.get_normalized_file_name().to_ascii_lowercase();
+				// keep the first entry if a file name is used twice, like a linear search would
+				index.entry(key).or_insert(i);
+			}
+			index
+		});
+
+		let entry_index = *index.get(&normalize_path(path))?;
+		self.entries.get(entry_index)
+	}
+
+	/// Get a reader for the unencrypted data of the file at a path, as found by [Self::find].
+	pub fn open<'file>(
+		&self,
+		path: &str,
+		file: impl Read + Seek + 'file,
+		game_version: GameVersion,
+	) -> std::io::Result<impl Read + Seek + 'file> {
+		let entry = self.find(path).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				format!("{path} is not in the .pak file"),
+			)
+		})?;
+		entry.get_reader_with_data_start(file, self.data_start, game_version)
+	}
+
+	/// Tries to detect which game a .pak file belongs to.
+	///
+	/// Every entry layout and pak key is tried on the first few entries. A candidate is valid if
+	/// all file names decrypt to ascii paths and all file data lies within the file. If multiple
+	/// candidates are valid, the one that decrypts the padding after the file names to the most
+	/// null bytes wins. Games that share the same layout and key can't be told apart, in that case
+	/// the oldest one is returned since they all read the file the same way.
+	///
+	/// The reader is returned to its original position afterwards.
+	pub fn detect_game_version(mut reader: impl Read + Seek) -> Result<GameVersion, PakReadError> {
+		/// The amount of entries to check for each candidate.
+		const SAMPLE_SIZE: u32 = 16;
+
+		let start_position = reader.stream_position()?;
+		let header = PakHeader::read(&mut reader)?;
+		let entries_start = reader.stream_position()?;
+		let stream_len = reader.seek(io::SeekFrom::End(0))?;
+
+		let sample_size = header.file_count.min(SAMPLE_SIZE);
+
+		let mut best: Option<(GameVersion, u32)> = None;
+		let mut checked_formats = vec![];
+		for game_version in GameVersion::iter() {
+			let pak_type = Self::get_pak_type(game_version);
+			let pak_key = Self::get_pak_key(game_version);
+
+			// games that share a format would give the same score
+			if checked_formats.contains(&(pak_type, pak_key)) {
+				continue;
+			}
+			checked_formats.push((pak_type, pak_key));
+
+			let entry_size = pak_type.entry_size();
+			let data_start = entries_start + entry_size * header.file_count as u64;
+			if data_start > stream_len {
+				trace!(?game_version, "entry table does not fit in file");
+				continue;
+			}
+
+			reader.seek(io::SeekFrom::Start(entries_start))?;
+			let mut score = Some(0);
+			let mut entry_bytes = vec![0u8; entry_size as usize];
+			for _ in 0..sample_size {
+				reader.read_exact(&mut entry_bytes)?;
+				let entry_score =
+					pak_type.score_raw_entry(&entry_bytes, pak_key, data_start, stream_len);
+				score = score.zip(entry_score).map(|(a, b)| a + b);
+			}
+			trace!(?game_version, ?score, "scored game version candidate");
+
+			if let Some(score) = score {
+				if best.is_none_or(|(_, best_score)| score > best_score) {
+					best = Some((game_version, score));
+				}
+			}
+		}
+
+		reader.seek(io::SeekFrom::Start(start_position))?;
+
+		let (game_version, _) = best.ok_or(PakReadError::UnknownGameVersion)?;
+		debug!("Detected game version: {:?}", game_version);
+		Ok(game_version)
+	}
+
 	fn get_pak_type(version: GameVersion) -> PakEntryType {
 		match version {
 			GameVersion::A17 => PakEntryType::Entry32,
@@ -127,8 +244,10 @@ impl GustPak {
 		}
 	}
 
-	/// Decrypts some data in-place.
-	fn decrypt(ciphertext: &mut [u8], file_key: &[u8], pak_key: Option<&[u8; 32]>) {
+	/// Combines a file key with the pak key to get the key used to encrypt the file's data.
+	///
+	/// Only the first `file_key.len()` bytes of the returned key are used.
+	fn get_xor_key(file_key: &[u8], pak_key: Option<&[u8; 32]>) -> Vec<u8> {
 		// default to null bytes if no pak_key was given
 		let mut pak_key = pak_key.cloned().unwrap_or_default();
 
@@ -142,7 +261,12 @@ impl GustPak {
 			*b ^= file_key[i % file_key.len()];
 		});
 
-		let xor_key = &pak_key[..file_key.len()];
+		pak_key[..file_key.len()].to_vec()
+	}
+
+	/// Decrypts some data in-place.
+	fn decrypt(ciphertext: &mut [u8], file_key: &[u8], pak_key: Option<&[u8; 32]>) {
+		let xor_key = Self::get_xor_key(file_key, pak_key);
 		// trace!("Decrypting name with xor key: {:?}", xor_key);
 
 		// xor ciphertext with xor_key
@@ -354,13 +478,30 @@ impl PakEntryList {
 		self.len() == 0
 	}
 
+	/// Gets the entry at the given index.
+	pub fn get(&self, index: usize) -> Option<PakEntryRef<'_>> {
+		match self {
+			PakEntryList::Entry32(v) => v.get(index).map(PakEntryRef::Entry32),
+			PakEntryList::Entry64(v) => v.get(index).map(PakEntryRef::Entry64),
+			PakEntryList::Entry64Ext(v) => v.get(index).map(PakEntryRef::Entry64Ext),
+		}
+	}
+
 	/// Creates an iterator over a common representation of the entries.
-	pub fn iter(&self) -> impl Iterator<Item = PakEntryRef> + '_ {
+	pub fn iter(&self) -> impl Iterator<Item = PakEntryRef<'_>> + '_ {
 		PakEntryIterator {
 			list: self,
 			index: 0,
 		}
 	}
+
+	/// Creates an iterator over the entries that are selected by a filter.
+	pub fn iter_filtered<'pak>(
+		&'pak self,
+		filter: &'pak PakEntryFilter,
+	) -> impl Iterator<Item = PakEntryRef<'pak>> + 'pak {
+		self.iter().filter(|entry| filter.matches(entry))
+	}
 }
 
 struct PakEntryIterator<'pak> {
@@ -401,7 +542,7 @@ pub enum PakEntry {
 }
 
 impl PakEntry {
-	pub fn as_ref(&self) -> PakEntryRef {
+	pub fn as_ref(&self) -> PakEntryRef<'_> {
 		match self {
 			PakEntry::Entry32(e) => PakEntryRef::Entry32(e),
 			PakEntry::Entry64(e) => PakEntryRef::Entry64(e),
@@ -428,6 +569,14 @@ impl<'pak> PakEntryRef<'pak> {
 		}
 	}
 
+	/// Gets the file name with `/` as separator and without the leading separator, like
+	/// `Data/x64/res_cmn/ui/a24_item_icon.g1t`.
+	pub fn get_normalized_file_name(&'pak self) -> String {
+		self.get_file_name()
+			.trim_start_matches('\\')
+			.replace('\\', "/")
+	}
+
 	/// Gets the file size
 	pub fn get_file_size(&'pak self) -> u32 {
 		match self {
@@ -472,33 +621,45 @@ impl<'pak> PakEntryRef<'pak> {
 		data_start: u64,
 		game_version: GameVersion,
 	) -> std::io::Result<impl Read + Seek + 'file> {
-		// default to null bytes if no pak_key was given
-		let mut pak_key = GustPak::get_pak_key(game_version)
-			.cloned()
-			.unwrap_or_default();
-
-		let file_key = self.get_file_key();
-
-		debug_assert!(
-			file_key.len() <= pak_key.len(),
-			"file key may not be larger than pak key"
-		);
-
-		// xor pak_key with file_key to get xor key
-		pak_key.iter_mut().enumerate().for_each(|(i, b)| {
-			*b ^= file_key[i % file_key.len()];
-		});
-
-		let xor_key = &pak_key[..file_key.len()];
+		let xor_key = GustPak::get_xor_key(self.get_file_key(), GustPak::get_pak_key(game_version));
 		trace!("Creating reader with xor key: {:?}", xor_key);
 
 		file.seek(io::SeekFrom::Start(data_start + self.get_data_offset()))?;
 		Ok(XorReader::new(
 			FencedReader::take(file, self.get_file_size() as u64)?,
-			xor_key,
+			&xor_key,
 		))
 	}
 
+	/// Get a reader for the file's unencrypted data, given the contents of the entire .pak file.
+	///
+	/// The reader only borrows `pak_data`, so unlike [Self::get_reader] it is cheap to clone and
+	/// can be used from multiple threads at once.
+	pub fn get_slice_reader<'data>(
+		&'pak self,
+		pak_data: &'data [u8],
+		pak: &'pak GustPak,
+		game_version: GameVersion,
+	) -> std::io::Result<SliceXorReader<'data>> {
+		let xor_key = GustPak::get_xor_key(self.get_file_key(), GustPak::get_pak_key(game_version));
+		trace!("Creating slice reader with xor key: {:?}", xor_key);
+
+		let start = pak.data_start.saturating_add(self.get_data_offset());
+		let end = start.saturating_add(self.get_file_size() as u64);
+		let data = usize::try_from(start)
+			.ok()
+			.zip(usize::try_from(end).ok())
+			.and_then(|(start, end)| pak_data.get(start..end))
+			.ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::UnexpectedEof,
+					"file data lies outside of the .pak file",
+				)
+			})?;
+
+		Ok(SliceXorReader::new(data, &xor_key))
+	}
+
 	pub fn into_owned(self) -> PakEntry {
 		match self {
 			PakEntryRef::Entry32(e) => PakEntry::Entry32(e.clone()),
@@ -508,9 +669,350 @@ impl<'pak> PakEntryRef<'pak> {
 	}
 }
 
-#[derive(Debug)]
+/// Normalizes a path the same way as [PakEntryRef::get_normalized_file_name], and lowercases it.
+pub(crate) fn normalize_path(path: &str) -> String {
+	path.replace('\\', "/")
+		.trim_matches('/')
+		.to_ascii_lowercase()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PakEntryType {
 	Entry32,
 	Entry64,
 	Entry64Ext,
 }
+
+impl PakEntryType {
+	/// The length of the per-file xor key.
+	fn file_key_len(&self) -> usize {
+		match self {
+			PakEntryType::Entry32 | PakEntryType::Entry64 => 20,
+			PakEntryType::Entry64Ext => 32,
+		}
+	}
+
+	/// The size of an entry in the entry table.
+	fn entry_size(&self) -> u64 {
+		match self {
+			PakEntryType::Entry32 => 160,
+			PakEntryType::Entry64 => 168,
+			PakEntryType::Entry64Ext => 184,
+		}
+	}
+
+	/// Scores a raw, still encrypted entry by decrypting it with the given pak key.
+	///
+	/// Returns `None` if the file name does not decrypt to something that looks like a path or if
+	/// the file data does not fit in the file. Otherwise, returns the amount of null bytes in the
+	/// padding after the file name, which is higher when the correct key is used.
+	///
+	/// This does not use the regular entry parsing code, since that fails on invalid file names.
+	fn score_raw_entry(
+		&self,
+		entry_bytes: &[u8],
+		pak_key: Option<&[u8; 32]>,
+		data_start: u64,
+		stream_len: u64,
+	) -> Option<u32> {
+		debug_assert_eq!(entry_bytes.len() as u64, self.entry_size());
+
+		let file_size = u32::from_le_bytes(
+			entry_bytes[128..132]
+				.try_into()
+				.expect("slice should be 4 bytes"),
+		) as u64;
+		let file_key = &entry_bytes[132..132 + self.file_key_len()];
+		let (data_offset, pak_key) = match self {
+			// older formats don't use the pak key for file names
+			PakEntryType::Entry32 => (
+				u32::from_le_bytes(entry_bytes[152..156].try_into().expect("4 bytes")) as u64,
+				None,
+			),
+			PakEntryType::Entry64 => (
+				u64::from_le_bytes(entry_bytes[152..160].try_into().expect("8 bytes")),
+				None,
+			),
+			PakEntryType::Entry64Ext => (
+				u64::from_le_bytes(entry_bytes[168..176].try_into().expect("8 bytes")),
+				pak_key,
+			),
+		};
+
+		let mut file_name_bytes = [0u8; 128];
+		file_name_bytes.copy_from_slice(&entry_bytes[..128]);
+		GustPak::decrypt(&mut file_name_bytes, file_key, pak_key);
+
+		let file_name = CStr::from_bytes_until_nul(&file_name_bytes)
+			.ok()?
+			.to_bytes();
+		let name_is_valid = file_name.contains(&b'\\')
+			&& file_name
+				.iter()
+				.all(|&c| (c.is_ascii_graphic() && !b":*?\"<>|".contains(&c)) || c == b' ');
+		if !name_is_valid {
+			return None;
+		}
+
+		let data_end = data_start
+			.checked_add(data_offset)?
+			.checked_add(file_size)?;
+		if data_end > stream_len {
+			return None;
+		}
+
+		let padding = &file_name_bytes[file_name.len()..];
+		Some(padding.iter().filter(|&&b| b == 0).count() as u32)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::*;
+	use crate::errors::PakFilterError;
+
+	fn write_test_pak(game_version: GameVersion) -> Vec<u8> {
+		let mut writer = GustPakWriter::new(game_version);
+		writer
+			.add_file(r"\Data\x64\res_cmn\ui\a24_item_icon.g1t", vec![1, 2, 3])
+			.unwrap();
+		writer
+			.add_file(r"\Data\x64\res_cmn\ui\a24_item_icon_2.g1t", vec![4, 5])
+			.unwrap();
+
+		let mut pak_bytes = vec![];
+		writer.write(&mut pak_bytes).unwrap();
+		pak_bytes
+	}
+
+	#[test]
+	fn detect_game_version_reads_correctly() {
+		for game_version in GameVersion::iter() {
+			let pak_bytes = write_test_pak(game_version);
+			let mut cursor = Cursor::new(&pak_bytes);
+
+			let detected = GustPak::detect_game_version(&mut cursor).unwrap();
+			assert_eq!(cursor.position(), 0, "reader should be rewound");
+
+			// games with the same format can't be told apart, but must read the same way
+			assert_eq!(
+				GustPak::get_pak_type(detected),
+				GustPak::get_pak_type(game_version)
+			);
+			assert_eq!(
+				GustPak::get_pak_key(detected),
+				GustPak::get_pak_key(game_version)
+			);
+
+			let pak = GustPak::read_index(&mut cursor, detected).unwrap();
+			let names = pak
+				.entries
+				.iter()
+				.map(|e| e.get_file_name().to_string())
+				.collect::<Vec<_>>();
+			assert_eq!(
+				names,
+				[
+					r"\Data\x64\res_cmn\ui\a24_item_icon.g1t",
+					r"\Data\x64\res_cmn\ui\a24_item_icon_2.g1t"
+				]
+			);
+		}
+	}
+
+	#[test]
+	fn detect_game_version_prefers_oldest_game() {
+		let pak_bytes = write_test_pak(GameVersion::A21);
+		let detected = GustPak::detect_game_version(Cursor::new(&pak_bytes)).unwrap();
+		assert_eq!(detected, GameVersion::A18);
+	}
+
+	#[test]
+	fn detect_game_version_rejects_garbage() {
+		let mut pak_bytes = write_test_pak(GameVersion::A24);
+		// corrupt the encrypted file names
+		pak_bytes[16..16 + 128].fill(0xAA);
+
+		let result = GustPak::detect_game_version(Cursor::new(&pak_bytes));
+		assert!(matches!(result, Err(PakReadError::UnknownGameVersion)));
+	}
+
+	#[test]
+	fn slice_reader_matches_file_reader() {
+		for game_version in GameVersion::iter() {
+			let pak_bytes = write_test_pak(game_version);
+			let pak = GustPak::read_index(Cursor::new(&pak_bytes), game_version).unwrap();
+
+			for entry in pak.entries.iter() {
+				let mut expected = vec![];
+				entry
+					.get_reader(Cursor::new(&pak_bytes), &pak, game_version)
+					.unwrap()
+					.read_to_end(&mut expected)
+					.unwrap();
+
+				let mut actual = vec![];
+				let reader = entry
+					.get_slice_reader(&pak_bytes, &pak, game_version)
+					.unwrap();
+				assert_eq!(reader.len(), entry.get_file_size() as usize);
+				reader.clone().read_to_end(&mut actual).unwrap();
+				assert_eq!(actual, expected, "{game_version:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn slice_reader_rejects_truncated_pak() {
+		let pak_bytes = write_test_pak(GameVersion::A24);
+		let pak = GustPak::read_index(Cursor::new(&pak_bytes), GameVersion::A24).unwrap();
+		let entry = pak.entries.iter().last().unwrap();
+
+		let result =
+			entry.get_slice_reader(&pak_bytes[..pak_bytes.len() - 1], &pak, GameVersion::A24);
+		assert!(result.is_err());
+	}
+
+	#[cfg(feature = "mmap")]
+	#[test]
+	fn mapped_pak_reads_entries_in_parallel() {
+		let path =
+			std::env::temp_dir().join(format!("gust-pak-mmap-test-{}.pak", std::process::id()));
+		std::fs::write(&path, write_test_pak(GameVersion::A24)).unwrap();
+
+		let file = std::fs::File::open(&path).unwrap();
+		let pak = MappedGustPak::open(&file, GameVersion::A24).unwrap();
+		let contents = std::thread::scope(|scope| {
+			let handles = pak
+				.entries()
+				.iter()
+				.map(|entry| {
+					let mut reader = pak.get_reader(entry).unwrap();
+					scope.spawn(move || {
+						let mut data = vec![];
+						reader.read_to_end(&mut data).unwrap();
+						data
+					})
+				})
+				.collect::<Vec<_>>();
+			handles
+				.into_iter()
+				.map(|handle| handle.join().unwrap())
+				.collect::<Vec<_>>()
+		});
+
+		drop(pak);
+		drop(file);
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(contents, [vec![1, 2, 3], vec![4, 5]]);
+	}
+
+	#[test]
+	fn iter_filtered_selects_entries() {
+		let mut writer = GustPakWriter::new(GameVersion::A24);
+		for file_name in [
+			r"\Data\x64\res_cmn\ui\a24_item_icon.g1t",
+			r"\Data\x64\res_cmn\ui\a24_item_icon.gmpk",
+			r"\Data\x64\res_cmn\model\pc01.G1T",
+			r"\Data\x64\res_cmn\model\pc01.g1m",
+		] {
+			writer.add_file(file_name, vec![0]).unwrap();
+		}
+		let mut pak_bytes = vec![];
+		writer.write(&mut pak_bytes).unwrap();
+		let pak = GustPak::read_index(Cursor::new(&pak_bytes), GameVersion::A24).unwrap();
+
+		let filtered_names = |filter: &PakEntryFilter| {
+			pak.entries
+				.iter_filtered(filter)
+				.map(|entry| entry.get_normalized_file_name())
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(filtered_names(&PakEntryFilter::default()).len(), 4);
+
+		let filter = PakEntryFilter {
+			include: vec![EntryPattern::glob("*.g1t").unwrap()],
+			exclude: vec![],
+		};
+		assert_eq!(
+			filtered_names(&filter),
+			[
+				"Data/x64/res_cmn/ui/a24_item_icon.g1t",
+				"Data/x64/res_cmn/model/pc01.G1T"
+			]
+		);
+
+		let filter = PakEntryFilter {
+			include: vec![EntryPattern::glob("Data/x64/res_cmn/ui/*").unwrap()],
+			exclude: vec![EntryPattern::regex(r"\.gmpk$").unwrap()],
+		};
+		assert_eq!(
+			filtered_names(&filter),
+			["Data/x64/res_cmn/ui/a24_item_icon.g1t"]
+		);
+
+		let filter = PakEntryFilter {
+			include: vec![],
+			exclude: vec![EntryPattern::regex("model/").unwrap()],
+		};
+		assert_eq!(filtered_names(&filter).len(), 2);
+
+		// regexes ignore case like globs do
+		let filter = PakEntryFilter {
+			include: vec![EntryPattern::regex(r"\.g1t$").unwrap()],
+			exclude: vec![],
+		};
+		assert_eq!(filtered_names(&filter).len(), 2);
+
+		assert!(matches!(
+			EntryPattern::glob("[a"),
+			Err(PakFilterError::InvalidGlob(..))
+		));
+		assert!(matches!(
+			EntryPattern::regex("("),
+			Err(PakFilterError::InvalidRegex(_))
+		));
+	}
+
+	#[test]
+	fn find_ignores_case_and_separators() {
+		let pak_bytes = write_test_pak(GameVersion::A24);
+		let pak = GustPak::read_index(Cursor::new(&pak_bytes), GameVersion::A24).unwrap();
+
+		for path in [
+			r"\Data\x64\res_cmn\ui\a24_item_icon_2.g1t",
+			r"data\X64\RES_CMN\ui\A24_ITEM_ICON_2.G1T",
+			"/Data/x64/res_cmn/ui/a24_item_icon_2.g1t",
+			"data/x64/res_cmn/ui/a24_item_icon_2.g1t",
+		] {
+			let entry = pak.find(path).unwrap();
+			assert_eq!(
+				entry.get_file_name(),
+				r"\Data\x64\res_cmn\ui\a24_item_icon_2.g1t"
+			);
+		}
+		assert!(pak.find("res_cmn/ui/a24_item_icon_2.g1t").is_none());
+		assert!(pak.find("Data/x64/res_cmn/ui").is_none());
+
+		let mut data = vec![];
+		pak.open(
+			"data/x64/res_cmn/ui/a24_item_icon.g1t",
+			Cursor::new(&pak_bytes),
+			GameVersion::A24,
+		)
+		.unwrap()
+		.read_to_end(&mut data)
+		.unwrap();
+		assert_eq!(data, [1, 2, 3]);
+
+		let error = pak
+			.open("missing.g1t", Cursor::new(&pak_bytes), GameVersion::A24)
+			.err()
+			.unwrap();
+		assert_eq!(error.kind(), io::ErrorKind::NotFound);
+	}
+}
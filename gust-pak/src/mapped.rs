@@ -0,0 +1,59 @@
+use std::{fs::File, io::Cursor};
+
+use gust_common::GameVersion;
+use memmap2::Mmap;
+
+use crate::{errors::PakReadError, GustPak, PakEntryList, PakEntryRef, SliceXorReader};
+
+/// A memory-mapped .pak file.
+///
+/// Entries are read straight from the mapped file instead of through a file handle, so many
+/// threads can read entries from the same .pak file at once.
+pub struct MappedGustPak {
+	pak: GustPak,
+	game_version: GameVersion,
+	mmap: Mmap,
+}
+
+impl MappedGustPak {
+	/// Maps a .pak file into memory and reads its index.
+	///
+	/// The file must not be modified while it is mapped, the data read from it is undefined
+	/// otherwise.
+	pub fn open(file: &File, game_version: GameVersion) -> Result<Self, PakReadError> {
+		// SAFETY: the mapping is only read from, and .pak files are not expected to change while
+		// they are being read
+		let mmap = unsafe { Mmap::map(file)? };
+		let pak = GustPak::read_index(Cursor::new(&mmap[..]), game_version)?;
+
+		Ok(Self {
+			pak,
+			game_version,
+			mmap,
+		})
+	}
+
+	/// The index of the .pak file.
+	pub fn pak(&self) -> &GustPak {
+		&self.pak
+	}
+
+	/// The file entries in the .pak file.
+	pub fn entries(&self) -> &PakEntryList {
+		&self.pak.entries
+	}
+
+	pub fn game_version(&self) -> GameVersion {
+		self.game_version
+	}
+
+	/// Get a reader for an entry's unencrypted data.
+	///
+	/// The reader borrows the mapped file, it is cheap to clone and can be sent to other threads.
+	pub fn get_reader<'pak>(
+		&'pak self,
+		entry: PakEntryRef<'pak>,
+	) -> std::io::Result<SliceXorReader<'pak>> {
+		entry.get_slice_reader(&self.mmap, &self.pak, self.game_version)
+	}
+}
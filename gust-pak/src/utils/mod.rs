@@ -1,5 +1,7 @@
 mod fenced_reader;
+mod slice_xor_reader;
 mod xor_reader;
 
 pub use fenced_reader::FencedReader;
+pub use slice_xor_reader::SliceXorReader;
 pub use xor_reader::XorReader;
@@ -0,0 +1,141 @@
+use std::io::{Read, Seek, SeekFrom};
+
+/// A reader that XORs a slice of data with a key up to 32 bytes in length.
+///
+/// Unlike [super::XorReader], this does not wrap another reader. It only borrows the data, so it is
+/// cheap to clone and can be shared between threads. Seeking is limited to the bounds of the
+/// slice, like [super::FencedReader].
+#[derive(Clone)]
+pub struct SliceXorReader<'data> {
+	data: &'data [u8],
+	position: usize,
+	key_buffer: [u8; 32],
+	key_len: usize,
+}
+
+impl<'data> SliceXorReader<'data> {
+	/// Create a new instance of `SliceXorReader` over the given data with the given key.
+	pub fn new(data: &'data [u8], key_slice: &[u8]) -> Self {
+		assert!(!key_slice.is_empty() && key_slice.len() <= 32);
+		let mut key_buffer = [0u8; 32];
+		key_buffer[..key_slice.len()].copy_from_slice(key_slice);
+		Self {
+			data,
+			position: 0,
+			key_buffer,
+			key_len: key_slice.len(),
+		}
+	}
+
+	/// The length of the data in bytes.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.data.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.data.is_empty()
+	}
+}
+
+impl Read for SliceXorReader<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let remaining = &self.data[self.position..];
+		let read = remaining.len().min(buf.len());
+
+		// decrypt into the read buffer
+		buf[..read]
+			.iter_mut()
+			.zip(&remaining[..read])
+			.enumerate()
+			.for_each(|(i, (b, encrypted))| {
+				*b = encrypted ^ self.key_buffer[(self.position + i) % self.key_len];
+			});
+
+		self.position += read;
+		Ok(read)
+	}
+}
+
+impl Seek for SliceXorReader<'_> {
+	fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+		let new_pos = match pos {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::End(offset) => (self.data.len() as u64).checked_add_signed(offset),
+			SeekFrom::Current(offset) => (self.position as u64).checked_add_signed(offset),
+		};
+
+		match new_pos {
+			Some(new_pos) if new_pos <= self.data.len() as u64 => {
+				self.position = new_pos as usize;
+				Ok(new_pos)
+			}
+			_ => Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"seek out of bounds",
+			)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ENCRYPTED: [u8; 13] = [
+		0x5a, 0x51, 0x3a, 0x14, 0x7d, 0x18, 0x76, 0x0f, 0x7d, 0x46, 0x3a, 0x1c, 0x33,
+	];
+
+	#[test]
+	fn read_text() {
+		let mut reader = SliceXorReader::new(&ENCRYPTED, &[0x12, 0x34, 0x56, 0x78]);
+		let mut buf = [0; 13];
+		reader.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"Hello, world!");
+
+		assert_eq!(reader.read(&mut buf).unwrap(), 0);
+	}
+
+	#[test]
+	fn read_text_unaligned_chunks_seek() {
+		let mut reader = SliceXorReader::new(&ENCRYPTED, &[0x12, 0x34, 0x56, 0x78]);
+
+		let mut buf = [0; 13];
+		assert_eq!(reader.seek(SeekFrom::Start(3)).unwrap(), 3);
+		reader.read_exact(&mut buf[3..8]).unwrap();
+
+		assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 10);
+		reader.read_exact(&mut buf[10..13]).unwrap();
+
+		assert_eq!(reader.seek(SeekFrom::Current(-13)).unwrap(), 0);
+		reader.read_exact(&mut buf[0..3]).unwrap();
+
+		assert_eq!(reader.seek(SeekFrom::Current(5)).unwrap(), 8);
+		reader.read_exact(&mut buf[8..10]).unwrap();
+
+		assert_eq!(&buf, b"Hello, world!");
+	}
+
+	#[test]
+	fn seek_out_of_bounds() {
+		let mut reader = SliceXorReader::new(&ENCRYPTED, &[0x12, 0x34, 0x56, 0x78]);
+		assert!(reader.seek(SeekFrom::Start(14)).is_err());
+		assert!(reader.seek(SeekFrom::End(1)).is_err());
+		assert!(reader.seek(SeekFrom::Current(-1)).is_err());
+		assert_eq!(reader.stream_position().unwrap(), 0);
+	}
+
+	#[test]
+	fn clones_read_independently() {
+		let mut reader = SliceXorReader::new(&ENCRYPTED, &[0x12, 0x34, 0x56, 0x78]);
+		reader.seek(SeekFrom::Start(7)).unwrap();
+		let mut clone = reader.clone();
+
+		let mut buf = [0; 6];
+		reader.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"world!");
+		clone.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"world!");
+	}
+}
@@ -0,0 +1,483 @@
+use std::{
+	fs::File,
+	io::{self, Cursor, Write},
+	path::{Path, PathBuf},
+};
+
+use gust_common::GameVersion;
+use scroll::IOwrite;
+use tracing::{debug, trace};
+
+use crate::{
+	errors::PakWriteError,
+	utils::{FencedReader, XorReader},
+	GustPak, PakEntryRef, PakEntryType,
+};
+
+/// The maximum amount of files a .pak file can hold, as enforced when reading the header.
+const MAX_FILE_COUNT: usize = 0x10000;
+
+/// The size of the file name buffer in each entry, including the null terminator.
+const FILE_NAME_LEN: usize = 128;
+
+/// A builder for new .pak files.
+///
+/// Files are added to the writer first, after which [GustPakWriter::write] lays out the header,
+/// the entry table and the encrypted file data in one go. File data that was added from disk is
+/// only read when the archive is written.
+pub struct GustPakWriter {
+	game_version: GameVersion,
+	flags: u32,
+	entries: Vec<PakWriterEntry>,
+}
+
+struct PakWriterEntry {
+	file_name: String,
+	/// The file key, only the first [PakEntryType::file_key_len] bytes are used.
+	file_key: [u8; 32],
+	extra: u32,
+	flags: u64,
+	source: PakWriterSource,
+}
+
+enum PakWriterSource {
+	Bytes(Vec<u8>),
+	File { path: PathBuf, size: u64 },
+}
+
+impl PakWriterSource {
+	fn len(&self) -> u64 {
+		match self {
+			PakWriterSource::Bytes(data) => data.len() as u64,
+			PakWriterSource::File { size, .. } => *size,
+		}
+	}
+}
+
+impl GustPakWriter {
+	/// Creates a new, empty .pak writer for the given game.
+	pub fn new(game_version: GameVersion) -> Self {
+		Self {
+			game_version,
+			flags: 0,
+			entries: vec![],
+		}
+	}
+
+	/// Sets the flags stored in the .pak header.
+	pub fn set_flags(&mut self, flags: u32) {
+		self.flags = flags;
+	}
+
+	/// Returns the amount of files that have been added so far.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Adds a file with the given contents.
+	///
+	/// The file name should use the same format as [PakEntryRef::get_file_name], eg.
+	/// `\Data\x64\res_cmn\ui\a24_item_icon.g1t`. A file key is derived from the file name.
+	pub fn add_file(
+		&mut self,
+		file_name: impl Into<String>,
+		data: impl Into<Vec<u8>>,
+	) -> Result<(), PakWriteError> {
+		let file_name = file_name.into();
+		let file_key = Self::derive_file_key(&file_name);
+		self.push_entry(
+			file_name,
+			file_key,
+			0,
+			0,
+			PakWriterSource::Bytes(data.into()),
+		)
+	}
+
+	/// Adds a file that will be read from disk when the archive is written.
+	pub fn add_file_from_path(
+		&mut self,
+		file_name: impl Into<String>,
+		path: impl Into<PathBuf>,
+	) -> Result<(), PakWriteError> {
+		let file_name = file_name.into();
+		let path = path.into();
+		let size = std::fs::metadata(&path)?.len();
+		let file_key = Self::derive_file_key(&file_name);
+		self.push_entry(
+			file_name,
+			file_key,
+			0,
+			0,
+			PakWriterSource::File { path, size },
+		)
+	}
+
+	/// Adds an existing entry with new contents, keeping its file name, key and flags.
+	///
+	/// This allows re-packing an extracted .pak file without changing its index.
+	pub fn add_entry(
+		&mut self,
+		entry: PakEntryRef,
+		data: impl Into<Vec<u8>>,
+	) -> Result<(), PakWriteError> {
+		let mut file_key = [0u8; 32];
+		let entry_key = entry.get_file_key();
+		file_key[..entry_key.len()].copy_from_slice(entry_key);
+
+		let (extra, flags) = match entry {
+			PakEntryRef::Entry32(e) => (0, e.flags as u64),
+			PakEntryRef::Entry64(e) => (0, e.flags),
+			PakEntryRef::Entry64Ext(e) => (e.extra, e.flags),
+		};
+
+		self.push_entry(
+			entry.get_file_name().to_string(),
+			file_key,
+			extra,
+			flags,
+			PakWriterSource::Bytes(data.into()),
+		)
+	}
+
+	/// Recursively adds all files in a directory, eg. one that was created by extracting a .pak
+	/// file.
+	///
+	/// Paths are stored relative to `root` with a leading backslash and backslashes as separators,
+	/// and files are added in sorted order so the output does not depend on the file system.
+	pub fn add_directory(&mut self, root: impl AsRef<Path>) -> Result<(), PakWriteError> {
+		let root = root.as_ref();
+
+		let mut paths = vec![];
+		Self::collect_files(root, &mut paths)?;
+		paths.sort();
+		debug!("Found {} files in {:?}", paths.len(), root);
+
+		for path in paths {
+			let relative_path = path
+				.strip_prefix(root)
+				.expect("collected path should be inside root");
+
+			let mut file_name = String::new();
+			for component in relative_path.components() {
+				let component = component.as_os_str().to_str().ok_or_else(|| {
+					PakWriteError::FileNameNotAscii(relative_path.to_string_lossy().into_owned())
+				})?;
+				file_name.push('\\');
+				file_name.push_str(component);
+			}
+
+			trace!(?path, ?file_name, "adding file");
+			self.add_file_from_path(file_name, path)?;
+		}
+
+		Ok(())
+	}
+
+	fn collect_files(directory: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+		for entry in std::fs::read_dir(directory)? {
+			let entry = entry?;
+			let file_type = entry.file_type()?;
+			if file_type.is_dir() {
+				Self::collect_files(&entry.path(), paths)?;
+			} else if file_type.is_file() {
+				paths.push(entry.path());
+			}
+		}
+
+		Ok(())
+	}
+
+	fn push_entry(
+		&mut self,
+		file_name: String,
+		file_key: [u8; 32],
+		extra: u32,
+		flags: u64,
+		source: PakWriterSource,
+	) -> Result<(), PakWriteError> {
+		if !file_name.is_ascii() {
+			return Err(PakWriteError::FileNameNotAscii(file_name));
+		}
+
+		// leave room for the null terminator
+		if file_name.len() >= FILE_NAME_LEN || file_name.contains('\0') {
+			return Err(PakWriteError::InvalidFileName(file_name));
+		}
+
+		if source.len() > u32::MAX as u64 {
+			return Err(PakWriteError::FileTooLarge(file_name, source.len()));
+		}
+
+		if self.entries.len() >= MAX_FILE_COUNT {
+			return Err(PakWriteError::TooManyFiles(self.entries.len() + 1));
+		}
+
+		self.entries.push(PakWriterEntry {
+			file_name,
+			file_key,
+			extra,
+			flags,
+			source,
+		});
+
+		Ok(())
+	}
+
+	/// Writes the .pak file.
+	pub fn write(&self, mut writer: impl Write) -> Result<(), PakWriteError> {
+		let pak_type = GustPak::get_pak_type(self.game_version);
+		let pak_key = GustPak::get_pak_key(self.game_version);
+		debug!("Using game version: {:?}", self.game_version);
+		debug!("Using pak type: {:?}", pak_type);
+
+		// header
+		writer.iowrite(0x20000u32)?;
+		writer.iowrite(self.entries.len() as u32)?;
+		writer.iowrite(16u32)?;
+		writer.iowrite(self.flags)?;
+
+		// entry table, data offsets are relative to the end of this table
+		let mut data_offset = 0u64;
+		for entry in &self.entries {
+			let key_len = pak_type.file_key_len();
+			let file_key = &entry.file_key[..key_len];
+
+			let mut file_name_bytes = [0u8; FILE_NAME_LEN];
+			file_name_bytes[..entry.file_name.len()].copy_from_slice(entry.file_name.as_bytes());
+			// xor is symmetric, so decrypting the plaintext name encrypts it
+			GustPak::decrypt(&mut file_name_bytes, file_key, pak_key);
+
+			let file_size = entry.source.len() as u32;
+
+			writer.write_all(&file_name_bytes)?;
+			writer.iowrite(file_size)?;
+			writer.write_all(file_key)?;
+			match pak_type {
+				PakEntryType::Entry32 => {
+					let data_offset = u32::try_from(data_offset)
+						.map_err(|_| PakWriteError::DataOffsetTooLarge(data_offset))?;
+					writer.iowrite(data_offset)?;
+					writer.iowrite(entry.flags as u32)?;
+				}
+				PakEntryType::Entry64 => {
+					writer.iowrite(data_offset)?;
+					writer.iowrite(entry.flags)?;
+				}
+				PakEntryType::Entry64Ext => {
+					writer.iowrite(entry.extra)?;
+					writer.iowrite(data_offset)?;
+					writer.iowrite(entry.flags)?;
+				}
+			}
+
+			trace!(
+				file_name = entry.file_name,
+				data_offset,
+				file_size,
+				"wrote entry"
+			);
+			data_offset += file_size as u64;
+		}
+
+		// file data
+		for entry in &self.entries {
+			let xor_key = GustPak::get_xor_key(&entry.file_key[..pak_type.file_key_len()], pak_key);
+			let expected_len = entry.source.len();
+
+			let written = match &entry.source {
+				PakWriterSource::Bytes(data) => io::copy(
+					&mut XorReader::new(Cursor::new(data), &xor_key),
+					&mut writer,
+				)?,
+				PakWriterSource::File { path, .. } => {
+					let file = File::open(path)?;
+					let file = FencedReader::take(file, expected_len)?;
+					io::copy(&mut XorReader::new(file, &xor_key), &mut writer)?
+				}
+			};
+
+			if written != expected_len {
+				return Err(PakWriteError::FileSizeChanged(
+					entry.file_name.clone(),
+					expected_len,
+					written,
+				));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Derives a file key from the file name.
+	///
+	/// The games don't care what the key is, but the official .pak files never use an empty key.
+	/// Deriving it from the name keeps the output reproducible.
+	fn derive_file_key(file_name: &str) -> [u8; 32] {
+		// FNV-1a to seed a xorshift generator
+		let mut state = file_name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+			(hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+		});
+
+		let mut file_key = [0u8; 32];
+		for chunk in file_key.chunks_exact_mut(8) {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			chunk.copy_from_slice(&state.to_le_bytes());
+		}
+
+		file_key
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Read};
+
+	use gust_common::strum::IntoEnumIterator;
+
+	use super::*;
+
+	fn test_files() -> Vec<(&'static str, Vec<u8>)> {
+		vec![
+			(
+				r"\Data\x64\res_cmn\ui\a24_item_icon.g1t",
+				b"G1TG0060".to_vec(),
+			),
+			(r"\Data\x64\empty.bin", vec![]),
+			(
+				r"\Data\x64\saves\large.bin",
+				(0..10_000u32).map(|i| (i * 7 % 251) as u8).collect(),
+			),
+		]
+	}
+
+	fn read_entries(pak_bytes: &[u8], game_version: GameVersion) -> Vec<(String, Vec<u8>)> {
+		let mut cursor = Cursor::new(pak_bytes);
+		let pak = GustPak::read_index(&mut cursor, game_version).unwrap();
+
+		pak.entries
+			.iter()
+			.map(|entry| {
+				let mut data = vec![];
+				entry
+					.get_reader(&mut cursor, &pak, game_version)
+					.unwrap()
+					.read_to_end(&mut data)
+					.unwrap();
+				(entry.get_file_name().to_string(), data)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn write_and_read_all_games() {
+		for game_version in GameVersion::iter() {
+			let mut writer = GustPakWriter::new(game_version);
+			for (name, data) in test_files() {
+				writer.add_file(name, data).unwrap();
+			}
+
+			let mut pak_bytes = vec![];
+			writer.write(&mut pak_bytes).unwrap();
+
+			let entries = read_entries(&pak_bytes, game_version);
+			let expected = test_files()
+				.into_iter()
+				.map(|(name, data)| (name.to_string(), data))
+				.collect::<Vec<_>>();
+			assert_eq!(entries, expected, "mismatch for {game_version:?}");
+		}
+	}
+
+	/// read_index → extract → write → read_index should give back the same archive.
+	#[test]
+	fn round_trip_is_identical() {
+		for game_version in GameVersion::iter() {
+			let mut writer = GustPakWriter::new(game_version);
+			for (name, data) in test_files() {
+				writer.add_file(name, data).unwrap();
+			}
+			let mut original = vec![];
+			writer.write(&mut original).unwrap();
+
+			let mut cursor = Cursor::new(&original);
+			let pak = GustPak::read_index(&mut cursor, game_version).unwrap();
+			let mut writer = GustPakWriter::new(game_version);
+			for entry in pak.entries.iter() {
+				let mut data = vec![];
+				entry
+					.get_reader(&mut cursor, &pak, game_version)
+					.unwrap()
+					.read_to_end(&mut data)
+					.unwrap();
+				writer.add_entry(entry, data).unwrap();
+			}
+			let mut rewritten = vec![];
+			writer.write(&mut rewritten).unwrap();
+
+			assert_eq!(
+				read_entries(&original, game_version),
+				read_entries(&rewritten, game_version)
+			);
+			assert_eq!(original, rewritten, "mismatch for {game_version:?}");
+		}
+	}
+
+	#[test]
+	fn add_directory_uses_pak_paths() {
+		let root = std::env::temp_dir().join(format!("gust-pak-writer-{}", std::process::id()));
+		std::fs::create_dir_all(root.join("Data").join("x64")).unwrap();
+		std::fs::write(root.join("Data").join("x64").join("b.bin"), b"bbb").unwrap();
+		std::fs::write(root.join("Data").join("a.bin"), b"a").unwrap();
+
+		let mut writer = GustPakWriter::new(GameVersion::A24);
+		writer.add_directory(&root).unwrap();
+		let mut pak_bytes = vec![];
+		writer.write(&mut pak_bytes).unwrap();
+		std::fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(
+			read_entries(&pak_bytes, GameVersion::A24),
+			vec![
+				(r"\Data\a.bin".to_string(), b"a".to_vec()),
+				(r"\Data\x64\b.bin".to_string(), b"bbb".to_vec()),
+			]
+		);
+	}
+
+	#[test]
+	fn file_data_is_encrypted() {
+		let mut writer = GustPakWriter::new(GameVersion::A24);
+		writer
+			.add_file(r"\test.txt", b"Hello, world!".to_vec())
+			.unwrap();
+
+		let mut pak_bytes = vec![];
+		writer.write(&mut pak_bytes).unwrap();
+
+		assert!(!pak_bytes.windows(13).any(|w| w == b"Hello, world!"));
+		assert!(!pak_bytes.windows(9).any(|w| w == b"\\test.txt"));
+	}
+
+	#[test]
+	fn reject_long_file_name() {
+		let mut writer = GustPakWriter::new(GameVersion::A24);
+		let result = writer.add_file("a".repeat(FILE_NAME_LEN), vec![]);
+		assert!(matches!(result, Err(PakWriteError::InvalidFileName(_))));
+	}
+
+	#[test]
+	fn reject_non_ascii_file_name() {
+		let mut writer = GustPakWriter::new(GameVersion::A24);
+		let result = writer.add_file(r"\ライザ.g1t", vec![]);
+		assert!(matches!(result, Err(PakWriteError::FileNameNotAscii(_))));
+	}
+}